@@ -1,30 +1,113 @@
-use lemon_sand_core::cell::Cell;
-use lemon_sand_core::sandbox::Sandbox;
+use crate::camera::Camera;
+use crate::clock::{self, Clock};
+use crate::gui::Gui;
+use crate::history::History;
+use lemon_sand_core::brush::{self, Brush, BrushAction};
+use lemon_sand_core::cell::{Cell, CellType};
+use lemon_sand_core::material::MaterialRegistry;
+use lemon_sand_core::sandbox::{RenderMode, Sandbox};
 use pixels::{Pixels, SurfaceTexture};
+use std::collections::HashSet;
 use std::sync::Arc;
 use winit::application::ApplicationHandler;
 use winit::dpi::{LogicalSize, PhysicalPosition};
-use winit::event::{ElementState, MouseButton, WindowEvent};
+use winit::event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent};
 use winit::event_loop::ActiveEventLoop;
-use winit::keyboard::{KeyCode, PhysicalKey};
+use winit::keyboard::{KeyCode, ModifiersState, PhysicalKey};
 use winit::window::{Window, WindowId};
 
-#[derive(Debug, Default, Clone, Copy)]
+const HEAT_PER_TICK: f32 = 10.0;
+const QUICK_SAVE_PATH: &str = "quicksave.lsnd";
+/// Zoom factor per line of mouse wheel movement.
+const ZOOM_PER_SCROLL_LINE: f32 = 1.25;
+/// Pixels of touchpad scrolling counted as one line.
+const PIXELS_PER_SCROLL_LINE: f32 = 50.0;
+/// Largest initial window size, bigger sandboxes start zoomed out.
+const MAX_WINDOW_SIZE: (f64, f64) = (1600.0, 900.0);
+
+/// Materials selectable with the number keys, every other one is reachable with tab.
+const HOTBAR: [CellType; 8] = [
+    CellType::SAND,
+    CellType::WATER,
+    CellType::STONE,
+    CellType::WALL,
+    CellType::METAL,
+    CellType::WOOD,
+    CellType::OIL,
+    CellType::FIRE,
+];
+
+#[derive(Debug, Clone, Copy)]
 pub enum PlaceMode {
+    Material(CellType),
+    Heat,
+    Cool,
+    Erase,
+}
+
+impl Default for PlaceMode {
+    fn default() -> Self {
+        Self::Material(CellType::SAND)
+    }
+}
+
+impl PlaceMode {
+    /// Whether holding the brush keeps acting on the same cells every simulated tick.
+    fn is_timed(self) -> bool {
+        matches!(self, Self::Heat | Self::Cool)
+    }
+}
+
+/// The shape painted with the current place mode.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    /// Paints along the cursor while the mouse is held.
     #[default]
-    Sand,
-    Water,
+    Freehand,
+    /// Shapes are painted from where the mouse was pressed to where it's released.
+    Line,
+    Rect,
+    HollowRect,
+    /// Paints the connected area of the clicked material.
+    Fill,
+}
+
+impl Tool {
+    const ALL: [(Self, &'static str); 5] = [
+        (Self::Freehand, "Freehand"),
+        (Self::Line, "Line"),
+        (Self::Rect, "Rect"),
+        (Self::HollowRect, "Hollow rect"),
+        (Self::Fill, "Fill"),
+    ];
+
+    fn is_shape(self) -> bool {
+        matches!(self, Self::Line | Self::Rect | Self::HollowRect)
+    }
 }
 
 pub struct App {
     window: Option<Arc<Window>>,
     pixels: Option<Pixels<'static>>,
+    gui: Option<Gui>,
     pub sandbox: Sandbox,
     paused: bool,
+    clock: Clock,
+    camera: Camera,
+    render_mode: RenderMode,
     cursor_pos: PhysicalPosition<f64>,
     cursor_pressed: bool,
+    /// Whether the view is being dragged with the middle mouse button.
+    panning: bool,
+    /// Cell the current stroke started at and the one it was last painted at.
+    stroke_start: Option<(isize, isize)>,
+    stroke_last: Option<(isize, isize)>,
     place_mode: PlaceMode,
     place_radius: u8,
+    tool: Tool,
+    replace_only: Option<CellType>,
+    history: History,
+    modifiers: ModifiersState,
 }
 
 impl App {
@@ -32,70 +115,435 @@ impl App {
         Self {
             window: None,
             pixels: None,
+            gui: None,
             sandbox: Sandbox::new(width, height),
             paused: false,
+            clock: Clock::default(),
+            camera: Camera::default(),
+            render_mode: RenderMode::default(),
             cursor_pos: PhysicalPosition::default(),
             cursor_pressed: false,
+            panning: false,
+            stroke_start: None,
+            stroke_last: None,
             place_mode: PlaceMode::default(),
             place_radius: 0,
+            tool: Tool::default(),
+            replace_only: None,
+            history: History::default(),
+            modifiers: ModifiersState::default(),
         }
     }
 
-    fn place(&mut self, x: isize, y: isize) {
-        let cell = match self.place_mode {
-            PlaceMode::Sand => Cell::sand(),
-            PlaceMode::Water => Cell::water(),
+    fn brush(&self) -> Brush {
+        let action = match self.place_mode {
+            PlaceMode::Material(type_) => BrushAction::Place(type_),
+            PlaceMode::Heat => BrushAction::Heat(HEAT_PER_TICK),
+            PlaceMode::Cool => BrushAction::Heat(-HEAT_PER_TICK),
+            PlaceMode::Erase => BrushAction::Erase,
         };
 
-        let r = self.place_radius as isize;
-        for dy in -r..=r {
-            for dx in -r..=r {
-                if dx * dx + dy * dy <= r * r {
-                    self.sandbox.place(x + dx, y + dy, cell);
-                }
+        Brush {
+            action,
+            replace_only: self.replace_only,
+        }
+    }
+
+    fn paint(&mut self, cells: Vec<(isize, isize)>) {
+        self.brush()
+            .paint(&mut self.sandbox, cells, |sandbox, x, y| {
+                self.history.record(sandbox, x, y)
+            });
+    }
+
+    /// The cells the current shape tool covers between both points.
+    fn shape(&self, start: (isize, isize), end: (isize, isize)) -> Vec<(isize, isize)> {
+        let radius = self.place_radius as usize;
+        match self.tool {
+            Tool::Line => brush::stroke(start, end, radius),
+            Tool::Rect => brush::rect(start, end),
+            Tool::HollowRect => brush::hollow_rect(start, end, radius + 1),
+            Tool::Freehand | Tool::Fill => Vec::new(),
+        }
+    }
+
+    fn start_stroke(&mut self) {
+        self.cursor_pressed = true;
+        self.stroke_start = self.cursor_coordinates();
+        self.stroke_last = None;
+
+        if self.tool == Tool::Fill
+            && let Some(start) = self.stroke_start
+        {
+            self.paint(brush::flood_fill(&self.sandbox, start));
+        }
+    }
+
+    fn end_stroke(&mut self) {
+        if !self.cursor_pressed {
+            return;
+        }
+
+        if self.tool.is_shape()
+            && let Some(start) = self.stroke_start
+            && let Some(end) = self.cursor_coordinates()
+        {
+            self.paint(self.shape(start, end));
+        }
+
+        self.cursor_pressed = false;
+        self.stroke_start = None;
+        self.history.finish_stroke();
+    }
+
+    /// Connects the cursor to where it was last frame, so fast movements don't leave gaps.
+    fn continue_stroke(&mut self) {
+        if self.tool != Tool::Freehand || self.place_mode.is_timed() {
+            return;
+        }
+
+        let Some(current) = self.cursor_coordinates() else {
+            return;
+        };
+
+        let last = self.stroke_last.unwrap_or(current);
+        self.paint(brush::stroke(last, current, self.place_radius as usize));
+        self.stroke_last = Some(current);
+    }
+
+    /// Heats or cools the cells along the cursor's path since the last tick, once per
+    /// simulated tick so the effect doesn't depend on the frame rate.
+    fn continue_timed_stroke(&mut self) {
+        if !self.cursor_pressed || self.tool != Tool::Freehand || !self.place_mode.is_timed() {
+            return;
+        }
+
+        let Some(current) = self.cursor_coordinates() else {
+            return;
+        };
+
+        let last = self.stroke_last.unwrap_or(current);
+        self.paint(brush::stroke(last, current, self.place_radius as usize));
+        self.stroke_last = Some(current);
+    }
+
+    /// The cells a shape would cover if the mouse was released now.
+    fn shape_preview(&self) -> Vec<(isize, isize)> {
+        match (self.stroke_start, self.cursor_coordinates()) {
+            (Some(start), Some(end)) => self.shape(start, end),
+            _ => Vec::new(),
+        }
+    }
+
+    fn select_material(&mut self, type_: CellType) {
+        self.place_mode = PlaceMode::Material(type_);
+        tracing::info!("Selected {}", type_.name());
+    }
+
+    /// Cycles through all registered materials, skipping empty.
+    fn select_next_material(&mut self) {
+        let registry = MaterialRegistry::global();
+        let current = match self.place_mode {
+            PlaceMode::Material(type_) => registry.iter().position(|(t, _)| t == type_),
+            _ => None,
+        };
+
+        let next = current.map_or(0, |i| i % (registry.len() - 1));
+        if let Some((type_, _)) = registry.iter().nth(next + 1) {
+            self.select_material(type_);
+        }
+    }
+
+    fn quick_save(&self) {
+        match self.sandbox.save(QUICK_SAVE_PATH) {
+            Ok(()) => tracing::info!("Saved sandbox to {QUICK_SAVE_PATH}"),
+            Err(err) => tracing::error!("Failed to save sandbox: {err}"),
+        }
+    }
+
+    fn quick_load(&mut self) {
+        match Sandbox::load(QUICK_SAVE_PATH) {
+            Ok(sandbox)
+                if sandbox.width() == self.sandbox.width()
+                    && sandbox.height() == self.sandbox.height() =>
+            {
+                self.history.snapshot(&self.sandbox);
+                self.sandbox = sandbox;
+                tracing::info!("Loaded sandbox from {QUICK_SAVE_PATH}");
             }
+            Ok(sandbox) => tracing::error!(
+                "Saved sandbox is {}x{}, expected {}x{}",
+                sandbox.width(),
+                sandbox.height(),
+                self.sandbox.width(),
+                self.sandbox.height()
+            ),
+            Err(err) => tracing::error!("Failed to load sandbox: {err}"),
+        }
+    }
+
+    fn toggle_pause(&mut self) {
+        // Remember the world before simulating, so everything that happens until the
+        // next pause can be undone in one go.
+        if self.paused {
+            self.history.snapshot(&self.sandbox);
+        }
+        self.paused = !self.paused;
+    }
+
+    /// Simulates a single tick, only while paused. Every step can be undone on its own.
+    fn step(&mut self) {
+        if self.paused {
+            self.history.snapshot(&self.sandbox);
+            self.continue_timed_stroke();
+            self.sandbox.update();
         }
     }
 
+    fn show_overlay(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Tools")
+            .default_pos([8.0, 8.0])
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let pause_label = if self.paused { "Resume" } else { "Pause" };
+                    if ui.button(pause_label).clicked() {
+                        self.toggle_pause();
+                    }
+                    if ui
+                        .add_enabled(self.paused, egui::Button::new("Step"))
+                        .clicked()
+                    {
+                        self.step();
+                    }
+                });
+                self.show_speed(ui);
+                self.show_render_mode(ui);
+                ui.add(egui::Slider::new(&mut self.place_radius, 0..=32).text("Brush size"));
+
+                ui.separator();
+                self.show_tools(ui);
+
+                ui.separator();
+                self.show_palette(ui);
+
+                ui.separator();
+                self.show_inspector(ui);
+            });
+    }
+
+    fn show_speed(&mut self, ui: &mut egui::Ui) {
+        ui.add_enabled(
+            self.clock.fast_forward.is_none(),
+            egui::Slider::new(
+                &mut self.clock.ticks_per_second,
+                clock::MIN_TICKS_PER_SECOND..=clock::MAX_TICKS_PER_SECOND,
+            )
+            .logarithmic(true)
+            .integer()
+            .text("Ticks per second"),
+        );
+
+        ui.horizontal(|ui| {
+            let mut fast_forward = self.clock.fast_forward.is_some();
+            if ui.checkbox(&mut fast_forward, "Fast-forward").changed() {
+                self.clock.toggle_fast_forward();
+            }
+
+            if let Some(ticks) = &mut self.clock.fast_forward {
+                ui.add(egui::Slider::new(ticks, 1..=clock::MAX_FAST_FORWARD).text("per frame"));
+            }
+        });
+    }
+
+    fn show_render_mode(&mut self, ui: &mut egui::Ui) {
+        let selected = RenderMode::ALL
+            .iter()
+            .find(|(mode, _)| *mode == self.render_mode)
+            .map_or("", |(_, label)| label);
+
+        egui::ComboBox::from_label("View")
+            .selected_text(selected)
+            .show_ui(ui, |ui| {
+                for (mode, label) in RenderMode::ALL {
+                    ui.selectable_value(&mut self.render_mode, mode, label);
+                }
+            });
+    }
+
+    /// Switches to the next render mode, wrapping around to the material colours.
+    fn cycle_render_mode(&mut self) {
+        let current = RenderMode::ALL
+            .iter()
+            .position(|(mode, _)| *mode == self.render_mode)
+            .unwrap_or_default();
+        let (mode, label) = RenderMode::ALL[(current + 1) % RenderMode::ALL.len()];
+        self.render_mode = mode;
+        tracing::info!("Showing {label}");
+    }
+
+    fn show_palette(&mut self, ui: &mut egui::Ui) {
+        egui::Grid::new("palette").num_columns(3).show(ui, |ui| {
+            let registry = MaterialRegistry::global();
+            for (i, (type_, material)) in registry.iter().skip(1).enumerate() {
+                let selected = matches!(self.place_mode, PlaceMode::Material(t) if t == type_);
+                let [r, g, b, _] = Cell::new(type_).color_rgba();
+
+                ui.horizontal(|ui| {
+                    let (rect, _) =
+                        ui.allocate_exact_size(egui::vec2(12.0, 12.0), egui::Sense::hover());
+                    ui.painter()
+                        .rect_filled(rect, 2.0, egui::Color32::from_rgb(r, g, b));
+                    if ui.selectable_label(selected, &material.name).clicked() {
+                        self.select_material(type_);
+                    }
+                });
+
+                if i % 3 == 2 {
+                    ui.end_row();
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            if ui
+                .selectable_label(matches!(self.place_mode, PlaceMode::Heat), "Heat")
+                .clicked()
+            {
+                self.place_mode = PlaceMode::Heat;
+            }
+            if ui
+                .selectable_label(matches!(self.place_mode, PlaceMode::Cool), "Cool")
+                .clicked()
+            {
+                self.place_mode = PlaceMode::Cool;
+            }
+            if ui
+                .selectable_label(matches!(self.place_mode, PlaceMode::Erase), "Eraser")
+                .clicked()
+            {
+                self.place_mode = PlaceMode::Erase;
+            }
+        });
+    }
+
+    fn show_tools(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal_wrapped(|ui| {
+            for (tool, label) in Tool::ALL {
+                ui.selectable_value(&mut self.tool, tool, label);
+            }
+        });
+
+        ui.horizontal(|ui| {
+            let mut replace_only = self.replace_only.is_some();
+            if ui.checkbox(&mut replace_only, "Replace only").changed() {
+                self.replace_only = replace_only.then_some(CellType::EMPTY);
+            }
+
+            if let Some(target) = &mut self.replace_only {
+                egui::ComboBox::from_id_source("replace_only")
+                    .selected_text(target.name())
+                    .show_ui(ui, |ui| {
+                        for (type_, material) in MaterialRegistry::global().iter() {
+                            ui.selectable_value(target, type_, &material.name);
+                        }
+                    });
+            }
+        });
+    }
+
+    fn show_inspector(&self, ui: &mut egui::Ui) {
+        let hovered = self
+            .cursor_coordinates()
+            .and_then(|(x, y)| Some((x, y, self.sandbox.get(x, y)?)));
+        let Some((x, y, cell)) = hovered else {
+            ui.label("Hover a cell to inspect it");
+            return;
+        };
+
+        egui::Grid::new("inspector").num_columns(2).show(ui, |ui| {
+            ui.label("Position");
+            ui.label(format!("{x} {y}"));
+            ui.end_row();
+            ui.label("Material");
+            ui.label(cell.get_type().name());
+            ui.end_row();
+            ui.label("Temperature");
+            ui.label(format!("{:.1} °C", cell.temperature));
+            ui.end_row();
+            ui.label("Moisture");
+            ui.label(format!("{:.3}", cell.moisture));
+            ui.end_row();
+            ui.label("Velocity");
+            ui.label(format!("{:.2} {:.2}", cell.vx, cell.vy));
+            ui.end_row();
+            ui.label("Fuel");
+            ui.label(format!("{:.1}", cell.fuel));
+            ui.end_row();
+            ui.label("Latent heat");
+            ui.label(format!("{:.1}", cell.latent_heat));
+            ui.end_row();
+            ui.label("Awake");
+            ui.label(if self.sandbox.is_awake(x, y) {
+                "yes"
+            } else {
+                "no"
+            });
+            ui.end_row();
+        });
+    }
+
+    /// The sandbox cell under the cursor, if there is one.
     fn cursor_coordinates(&self) -> Option<(isize, isize)> {
-        if let Some(pixels) = &self.pixels
-            && let Ok((x, y)) =
-                pixels.window_pos_to_pixel((self.cursor_pos.x as f32, self.cursor_pos.y as f32))
-        {
-            Some((x as isize, y as isize))
-        } else {
-            None
+        let (x, y) = self
+            .camera
+            .cell_at((self.cursor_pos.x as f32, self.cursor_pos.y as f32));
+        self.sandbox.get(x, y).map(|_| (x, y))
+    }
+
+    /// Shows the whole sandbox in the window.
+    fn reset_camera(&mut self) {
+        if let Some(window) = &self.window {
+            let size = window.inner_size();
+            self.camera = Camera::fit(
+                (self.sandbox.width(), self.sandbox.height()),
+                (size.width, size.height),
+            );
         }
     }
+
+    fn zoom(&mut self, delta: MouseScrollDelta) {
+        let lines = match delta {
+            MouseScrollDelta::LineDelta(_, y) => y,
+            MouseScrollDelta::PixelDelta(delta) => delta.y as f32 / PIXELS_PER_SCROLL_LINE,
+        };
+        self.camera.zoom_at(
+            (self.cursor_pos.x as f32, self.cursor_pos.y as f32),
+            ZOOM_PER_SCROLL_LINE.powf(lines),
+        );
+    }
 }
 
 impl ApplicationHandler for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        let (width, height) = (self.sandbox.width() as f64, self.sandbox.height() as f64);
+        let scale = (MAX_WINDOW_SIZE.0 / width)
+            .min(MAX_WINDOW_SIZE.1 / height)
+            .clamp(1.0, 3.0)
+            .floor();
         let window_attrs = Window::default_attributes()
             .with_title("Lemon Sand")
             .with_inner_size(LogicalSize::new(
-                self.sandbox.width() as f64 * 3.0,
-                self.sandbox.height() as f64 * 3.0,
-            ))
-            .with_min_inner_size(LogicalSize::new(
-                self.sandbox.width() as f64,
-                self.sandbox.height() as f64,
-            ))
-            .with_resize_increments(LogicalSize::new(
-                self.sandbox.width() as f64,
-                self.sandbox.height() as f64,
+                (width * scale).min(MAX_WINDOW_SIZE.0),
+                (height * scale).min(MAX_WINDOW_SIZE.1),
             ));
 
         let window = Arc::new(event_loop.create_window(window_attrs).unwrap());
 
+        // The buffer covers the whole window, the camera decides which cells end up in it.
         let size = window.inner_size();
         let surface = SurfaceTexture::new(size.width, size.height, window.clone());
-        let mut pixels = Pixels::new(
-            self.sandbox.width() as u32,
-            self.sandbox.height() as u32,
-            surface,
-        )
-        .unwrap();
+        let mut pixels = Pixels::new(size.width, size.height, surface).unwrap();
 
         pixels.clear_color(pixels::wgpu::Color {
             r: 0.1,
@@ -104,8 +552,10 @@ impl ApplicationHandler for App {
             a: 1.0,
         });
 
+        self.gui = Some(Gui::new(&window, &pixels));
         self.window = Some(window);
         self.pixels = Some(pixels);
+        self.reset_camera();
     }
 
     fn window_event(
@@ -114,35 +564,115 @@ impl ApplicationHandler for App {
         _window_id: WindowId,
         event: WindowEvent,
     ) {
+        if let Some(gui) = &mut self.gui {
+            gui.handle_event(&event);
+        }
+
         match event {
             WindowEvent::CloseRequested => event_loop.exit(),
             WindowEvent::RedrawRequested => {
+                // Taken out while drawing, so the overlay can access the rest of the app.
+                let Some(mut gui) = self.gui.take() else {
+                    return;
+                };
+                gui.prepare(|ctx| self.show_overlay(ctx));
+                let preview = self.shape_preview();
+
                 if let Some(pixels) = &mut self.pixels {
-                    self.sandbox.draw(pixels.frame_mut());
-                    pixels.render().unwrap();
+                    let width = pixels.texture().width() as usize;
+                    let camera = self.camera;
+                    self.sandbox.draw_region(
+                        pixels.frame_mut(),
+                        width,
+                        camera.origin,
+                        camera.zoom,
+                        self.render_mode,
+                    );
+                    highlight(pixels.frame_mut(), width, &camera, &preview);
+                    pixels
+                        .render_with(|encoder, render_target, context| {
+                            context.scaling_renderer.render(encoder, render_target);
+                            gui.render(encoder, render_target, context);
+                            Ok(())
+                        })
+                        .unwrap();
                 }
+
+                self.gui = Some(gui);
             }
-            WindowEvent::KeyboardInput { event, .. } => {
+            WindowEvent::KeyboardInput { event, .. }
+                if !self.gui.as_ref().is_some_and(Gui::wants_keyboard) =>
+            {
                 let PhysicalKey::Code(code) = event.physical_key else {
                     return;
                 };
 
+                if event.state != ElementState::Pressed {
+                    return;
+                }
+
                 match code {
-                    KeyCode::Digit1 => self.place_mode = PlaceMode::Sand,
-                    KeyCode::Digit2 => self.place_mode = PlaceMode::Water,
-                    KeyCode::Space => self.paused = !self.paused,
+                    KeyCode::Digit1 => self.select_material(HOTBAR[0]),
+                    KeyCode::Digit2 => self.select_material(HOTBAR[1]),
+                    KeyCode::Digit3 => self.select_material(HOTBAR[2]),
+                    KeyCode::Digit4 => self.select_material(HOTBAR[3]),
+                    KeyCode::Digit5 => self.select_material(HOTBAR[4]),
+                    KeyCode::Digit6 => self.select_material(HOTBAR[5]),
+                    KeyCode::Digit7 => self.select_material(HOTBAR[6]),
+                    KeyCode::Digit8 => self.select_material(HOTBAR[7]),
+                    KeyCode::Tab => self.select_next_material(),
+                    KeyCode::Digit9 => self.place_mode = PlaceMode::Heat,
+                    KeyCode::Digit0 => self.place_mode = PlaceMode::Cool,
+                    KeyCode::KeyE => self.place_mode = PlaceMode::Erase,
+                    KeyCode::KeyB => self.tool = Tool::Freehand,
+                    KeyCode::KeyL => self.tool = Tool::Line,
+                    KeyCode::KeyR => self.tool = Tool::Rect,
+                    KeyCode::KeyH => self.tool = Tool::HollowRect,
+                    KeyCode::KeyF => self.tool = Tool::Fill,
+                    KeyCode::Space => self.toggle_pause(),
+                    KeyCode::Period => self.step(),
+                    KeyCode::BracketRight => self.clock.speed_up(),
+                    KeyCode::BracketLeft => self.clock.slow_down(),
+                    KeyCode::KeyT => self.clock.toggle_fast_forward(),
                     KeyCode::ArrowUp => self.place_radius = self.place_radius.saturating_add(1),
                     KeyCode::ArrowDown => self.place_radius = self.place_radius.saturating_sub(1),
+                    KeyCode::F5 => self.quick_save(),
+                    KeyCode::F9 => self.quick_load(),
+                    KeyCode::Home => self.reset_camera(),
+                    KeyCode::KeyV => self.cycle_render_mode(),
+                    KeyCode::KeyZ if self.modifiers.control_key() => {
+                        if self.modifiers.shift_key() {
+                            self.history.redo(&mut self.sandbox, self.paused);
+                        } else {
+                            self.history.undo(&mut self.sandbox, self.paused);
+                        }
+                    }
                     _ => {}
                 }
             }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+            }
             WindowEvent::CursorMoved { position, .. } => {
+                if self.panning {
+                    self.camera.pan((
+                        (position.x - self.cursor_pos.x) as f32,
+                        (position.y - self.cursor_pos.y) as f32,
+                    ));
+                }
                 self.cursor_pos = position;
             }
+            WindowEvent::MouseWheel { delta, .. }
+                if !self.gui.as_ref().is_some_and(Gui::wants_pointer) =>
+            {
+                self.zoom(delta);
+            }
             WindowEvent::MouseInput { state, button, .. } => match button {
+                _ if state == ElementState::Pressed
+                    && self.gui.as_ref().is_some_and(Gui::wants_pointer) => {}
                 MouseButton::Left => match state {
-                    ElementState::Pressed => self.cursor_pressed = true,
-                    ElementState::Released => self.cursor_pressed = false,
+                    ElementState::Pressed => self.start_stroke(),
+                    ElementState::Released => self.end_stroke(),
                 },
                 MouseButton::Right => {
                     if state == ElementState::Pressed
@@ -152,6 +682,7 @@ impl ApplicationHandler for App {
                         tracing::info!("Cell at {x} {y} => {cell:#?}",);
                     }
                 }
+                MouseButton::Middle => self.panning = state == ElementState::Pressed,
                 _ => {}
             },
             WindowEvent::Resized(size) => {
@@ -160,6 +691,7 @@ impl ApplicationHandler for App {
                     && size.height > 0
                 {
                     pixels.resize_surface(size.width, size.height).unwrap();
+                    pixels.resize_buffer(size.width, size.height).unwrap();
                 }
             }
             _ => {}
@@ -167,13 +699,12 @@ impl ApplicationHandler for App {
     }
 
     fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
-        if self.cursor_pressed
-            && let Some((x, y)) = self.cursor_coordinates()
-        {
-            self.place(x, y);
+        if self.cursor_pressed {
+            self.continue_stroke();
         }
 
-        if !self.paused {
+        for _ in 0..self.clock.ticks(self.paused) {
+            self.continue_timed_stroke();
             self.sandbox.update();
         }
 
@@ -182,3 +713,36 @@ impl ApplicationHandler for App {
         }
     }
 }
+
+/// Brightens the pixels showing the given cells in a frame `frame_width` pixels wide.
+fn highlight(frame: &mut [u8], frame_width: usize, camera: &Camera, cells: &[(isize, isize)]) {
+    let (Some(min_x), Some(max_x)) = (
+        cells.iter().map(|cell| cell.0).min(),
+        cells.iter().map(|cell| cell.0).max(),
+    ) else {
+        return;
+    };
+    let min_y = cells.iter().map(|cell| cell.1).min().unwrap_or_default();
+    let max_y = cells.iter().map(|cell| cell.1).max().unwrap_or_default();
+
+    // Only the pixels around the cells are checked, zoomed out a pixel can show several cells.
+    let frame_height = frame.len() / (frame_width * 4).max(1);
+    let (left, top) = camera.cell_to_screen((min_x, min_y));
+    let (right, bottom) = camera.cell_to_screen((max_x + 1, max_y + 1));
+    let columns = (left.max(0.0) as usize)..(right.ceil().max(0.0) as usize).min(frame_width);
+    let rows = (top.max(0.0) as usize)..(bottom.ceil().max(0.0) as usize).min(frame_height);
+
+    let cells: HashSet<_> = cells.iter().copied().collect();
+    for py in rows {
+        for px in columns.clone() {
+            if !cells.contains(&camera.cell_at((px as f32, py as f32))) {
+                continue;
+            }
+
+            let index = (py * frame_width + px) * 4;
+            for channel in &mut frame[index..index + 3] {
+                *channel = (*channel / 2).saturating_add(128);
+            }
+        }
+    }
+}
@@ -0,0 +1,69 @@
+const MIN_ZOOM: f32 = 0.125;
+const MAX_ZOOM: f32 = 64.0;
+
+/// Which part of the sandbox is visible in the window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    /// Cell position at the top left corner of the window, can be fractional.
+    pub origin: (f32, f32),
+    /// Pixels per cell in both directions.
+    pub zoom: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            origin: (0.0, 0.0),
+            zoom: 1.0,
+        }
+    }
+}
+
+impl Camera {
+    /// Shows the whole sandbox centered in the viewport, at a whole zoom level if it fits that way.
+    pub fn fit(sandbox_size: (usize, usize), viewport: (u32, u32)) -> Self {
+        let (width, height) = (sandbox_size.0 as f32, sandbox_size.1 as f32);
+        let (viewport_width, viewport_height) = (viewport.0 as f32, viewport.1 as f32);
+
+        let zoom = (viewport_width / width).min(viewport_height / height);
+        let zoom = if zoom >= 1.0 { zoom.floor() } else { zoom }.clamp(MIN_ZOOM, MAX_ZOOM);
+
+        Self {
+            origin: (
+                (width - viewport_width / zoom) / 2.0,
+                (height - viewport_height / zoom) / 2.0,
+            ),
+            zoom,
+        }
+    }
+
+    /// The cell shown at the given pixel of the window.
+    pub fn cell_at(&self, pixel: (f32, f32)) -> (isize, isize) {
+        // Sampled at the pixel center, the same way the sandbox is drawn.
+        let x = self.origin.0 + (pixel.0.floor() + 0.5) / self.zoom;
+        let y = self.origin.1 + (pixel.1.floor() + 0.5) / self.zoom;
+        (x.floor() as isize, y.floor() as isize)
+    }
+
+    /// The window position the top left corner of the given cell is drawn at.
+    pub fn cell_to_screen(&self, cell: (isize, isize)) -> (f32, f32) {
+        (
+            (cell.0 as f32 - self.origin.0) * self.zoom,
+            (cell.1 as f32 - self.origin.1) * self.zoom,
+        )
+    }
+
+    /// Zooms by `factor`, keeping whatever is under the given window position in place.
+    pub fn zoom_at(&mut self, position: (f32, f32), factor: f32) {
+        let zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.origin.0 += position.0 / self.zoom - position.0 / zoom;
+        self.origin.1 += position.1 / self.zoom - position.1 / zoom;
+        self.zoom = zoom;
+    }
+
+    /// Moves the view along with the cursor being dragged by `delta` pixels.
+    pub fn pan(&mut self, delta: (f32, f32)) {
+        self.origin.0 -= delta.0 / self.zoom;
+        self.origin.1 -= delta.1 / self.zoom;
+    }
+}
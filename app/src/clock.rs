@@ -0,0 +1,72 @@
+use std::time::Instant;
+
+pub const DEFAULT_TICKS_PER_SECOND: f32 = 60.0;
+pub const MIN_TICKS_PER_SECOND: f32 = 1.0;
+pub const MAX_TICKS_PER_SECOND: f32 = 480.0;
+/// Ticks simulated per frame at most, slow frames let the simulation fall behind instead
+/// of taking even longer to catch up.
+const MAX_TICKS_PER_FRAME: u32 = 8;
+pub const DEFAULT_FAST_FORWARD: u32 = 16;
+pub const MAX_FAST_FORWARD: u32 = 128;
+
+/// Decides how many ticks to simulate each frame, so the speed doesn't depend on the frame rate.
+pub struct Clock {
+    pub ticks_per_second: f32,
+    /// Ticks simulated every frame instead, as fast as the machine allows.
+    pub fast_forward: Option<u32>,
+    /// Ticks owed to the simulation, the fraction is carried over to the next frame.
+    accumulated: f32,
+    last_frame: Instant,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self {
+            ticks_per_second: DEFAULT_TICKS_PER_SECOND,
+            fast_forward: None,
+            accumulated: 0.0,
+            last_frame: Instant::now(),
+        }
+    }
+}
+
+impl Clock {
+    /// The ticks to simulate this frame, time spent paused is skipped.
+    pub fn ticks(&mut self, paused: bool) -> u32 {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_frame).as_secs_f32();
+        self.last_frame = now;
+
+        if paused {
+            self.accumulated = 0.0;
+            return 0;
+        }
+
+        if let Some(ticks) = self.fast_forward {
+            self.accumulated = 0.0;
+            return ticks;
+        }
+
+        self.accumulated += elapsed * self.ticks_per_second;
+        let ticks = self.accumulated.floor();
+        self.accumulated -= ticks;
+        (ticks as u32).min(MAX_TICKS_PER_FRAME)
+    }
+
+    pub fn toggle_fast_forward(&mut self) {
+        self.fast_forward = match self.fast_forward {
+            Some(_) => None,
+            None => Some(DEFAULT_FAST_FORWARD),
+        };
+    }
+
+    /// Doubles the tick rate.
+    pub fn speed_up(&mut self) {
+        self.ticks_per_second = (self.ticks_per_second * 2.0).min(MAX_TICKS_PER_SECOND);
+    }
+
+    /// Halves the tick rate, down to slow motion.
+    pub fn slow_down(&mut self) {
+        self.ticks_per_second = (self.ticks_per_second / 2.0).max(MIN_TICKS_PER_SECOND);
+    }
+}
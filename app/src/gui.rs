@@ -0,0 +1,210 @@
+use egui::{ClippedPrimitive, Context, TexturesDelta, ViewportId};
+use egui_wgpu::{Renderer, ScreenDescriptor};
+use pixels::{Pixels, PixelsContext, wgpu};
+use winit::event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent};
+use winit::keyboard::Key;
+use winit::window::Window;
+
+/// Points scrolled per line of mouse wheel movement.
+const POINTS_PER_SCROLL_LINE: f32 = 50.0;
+
+/// Draws egui on top of the pixel buffer.
+/// Window events are translated by hand, egui-winit doesn't support the winit version used here.
+pub struct Gui {
+    ctx: Context,
+    input: egui::RawInput,
+    pointer_pos: egui::Pos2,
+    screen_descriptor: ScreenDescriptor,
+    renderer: Renderer,
+    paint_jobs: Vec<ClippedPrimitive>,
+    textures: TexturesDelta,
+}
+
+impl Gui {
+    pub fn new(window: &Window, pixels: &Pixels) -> Self {
+        let size = window.inner_size();
+        let input = egui::RawInput {
+            max_texture_side: Some(pixels.device().limits().max_texture_dimension_2d as usize),
+            ..Default::default()
+        };
+
+        Self {
+            ctx: Context::default(),
+            input,
+            pointer_pos: egui::Pos2::ZERO,
+            screen_descriptor: ScreenDescriptor {
+                size_in_pixels: [size.width, size.height],
+                pixels_per_point: window.scale_factor() as f32,
+            },
+            renderer: Renderer::new(pixels.device(), pixels.render_texture_format(), None, 1),
+            paint_jobs: Vec::new(),
+            textures: TexturesDelta::default(),
+        }
+    }
+
+    /// Whether the cursor is over the overlay or dragging one of its widgets.
+    pub fn wants_pointer(&self) -> bool {
+        self.ctx.wants_pointer_input() || self.ctx.is_pointer_over_area()
+    }
+
+    /// Whether one of the widgets has keyboard focus, like a text field being edited.
+    pub fn wants_keyboard(&self) -> bool {
+        self.ctx.wants_keyboard_input()
+    }
+
+    pub fn handle_event(&mut self, event: &WindowEvent) {
+        let pixels_per_point = self.screen_descriptor.pixels_per_point;
+        match event {
+            WindowEvent::Resized(size) => {
+                self.screen_descriptor.size_in_pixels = [size.width, size.height];
+            }
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.screen_descriptor.pixels_per_point = *scale_factor as f32;
+            }
+            WindowEvent::Focused(focused) => {
+                self.input.focused = *focused;
+                self.input.events.push(egui::Event::WindowFocused(*focused));
+            }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                let state = modifiers.state();
+                self.input.modifiers = egui::Modifiers {
+                    alt: state.alt_key(),
+                    ctrl: state.control_key(),
+                    shift: state.shift_key(),
+                    mac_cmd: false,
+                    command: state.control_key(),
+                };
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.pointer_pos = egui::pos2(
+                    position.x as f32 / pixels_per_point,
+                    position.y as f32 / pixels_per_point,
+                );
+                self.input
+                    .events
+                    .push(egui::Event::PointerMoved(self.pointer_pos));
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.input.events.push(egui::Event::PointerGone);
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                let button = match button {
+                    MouseButton::Left => egui::PointerButton::Primary,
+                    MouseButton::Right => egui::PointerButton::Secondary,
+                    MouseButton::Middle => egui::PointerButton::Middle,
+                    _ => return,
+                };
+
+                self.input.events.push(egui::Event::PointerButton {
+                    pos: self.pointer_pos,
+                    button,
+                    pressed: *state == ElementState::Pressed,
+                    modifiers: self.input.modifiers,
+                });
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let delta = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => {
+                        egui::vec2(*x, *y) * POINTS_PER_SCROLL_LINE
+                    }
+                    MouseScrollDelta::PixelDelta(delta) => {
+                        egui::vec2(delta.x as f32, delta.y as f32) / pixels_per_point
+                    }
+                };
+                self.input.events.push(egui::Event::Scroll(delta));
+            }
+            WindowEvent::KeyboardInput { event, .. } => {
+                let pressed = event.state == ElementState::Pressed;
+                if let Some(key) = egui_key(&event.logical_key) {
+                    self.input.events.push(egui::Event::Key {
+                        key,
+                        physical_key: None,
+                        pressed,
+                        repeat: false,
+                        modifiers: self.input.modifiers,
+                    });
+                }
+
+                // Shortcuts like ctrl+z don't type anything.
+                if pressed
+                    && !self.input.modifiers.ctrl
+                    && let Some(text) = &event.text
+                    && !text.chars().any(char::is_control)
+                {
+                    self.input.events.push(egui::Event::Text(text.to_string()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Runs the UI for this frame, has to happen before rendering.
+    pub fn prepare(&mut self, run_ui: impl FnOnce(&Context)) {
+        let [width, height] = self.screen_descriptor.size_in_pixels;
+        let pixels_per_point = self.screen_descriptor.pixels_per_point;
+        self.input.screen_rect = Some(egui::Rect::from_min_size(
+            egui::Pos2::ZERO,
+            egui::vec2(width as f32, height as f32) / pixels_per_point,
+        ));
+        self.input
+            .viewports
+            .entry(ViewportId::ROOT)
+            .or_default()
+            .native_pixels_per_point = Some(pixels_per_point);
+
+        let output = self.ctx.run(self.input.take(), run_ui);
+        self.textures.append(output.textures_delta);
+        self.paint_jobs = self.ctx.tessellate(output.shapes, output.pixels_per_point);
+    }
+
+    pub fn render(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        render_target: &wgpu::TextureView,
+        context: &PixelsContext,
+    ) {
+        for (id, image_delta) in &self.textures.set {
+            self.renderer
+                .update_texture(&context.device, &context.queue, *id, image_delta);
+        }
+        self.renderer.update_buffers(
+            &context.device,
+            &context.queue,
+            encoder,
+            &self.paint_jobs,
+            &self.screen_descriptor,
+        );
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("egui"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: render_target,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            self.renderer
+                .render(&mut render_pass, &self.paint_jobs, &self.screen_descriptor);
+        }
+
+        for id in std::mem::take(&mut self.textures).free {
+            self.renderer.free_texture(&id);
+        }
+    }
+}
+
+/// The egui key for a winit key, named keys are called the same in both.
+fn egui_key(key: &Key) -> Option<egui::Key> {
+    match key {
+        Key::Named(named) => egui::Key::from_name(&format!("{named:?}")),
+        Key::Character(text) => egui::Key::from_name(text),
+        _ => None,
+    }
+}
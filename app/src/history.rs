@@ -0,0 +1,166 @@
+use lemon_sand_core::cell::Cell;
+use lemon_sand_core::sandbox::Sandbox;
+use std::collections::{HashMap, VecDeque};
+
+/// Memory the undo history may use before the oldest edits are forgotten.
+const MEMORY_BUDGET: usize = 64 * 1024 * 1024;
+/// Memory a single cell of a stroke takes up.
+const STROKE_CELL_SIZE: usize = size_of::<((isize, isize), Cell)>();
+
+enum Edit {
+    /// Cells changed by a brush stroke along with the values they had before.
+    Stroke(Vec<((isize, isize), Cell)>),
+    /// The whole world, compressed using the save format.
+    Snapshot(Vec<u8>),
+}
+
+impl Edit {
+    fn size(&self) -> usize {
+        match self {
+            Self::Stroke(cells) => cells.len() * STROKE_CELL_SIZE,
+            Self::Snapshot(bytes) => bytes.len(),
+        }
+    }
+
+    /// Reverts the edit, returning the edit that brings the current state back.
+    fn revert(self, sandbox: &mut Sandbox) -> Option<Self> {
+        match self {
+            Self::Stroke(cells) => {
+                let current = cells
+                    .into_iter()
+                    .filter_map(|((x, y), cell)| {
+                        let current = sandbox.get(x, y)?;
+                        sandbox.place(x, y, cell);
+                        Some(((x, y), current))
+                    })
+                    .collect();
+                Some(Self::Stroke(current))
+            }
+            Self::Snapshot(bytes) => {
+                let current = Self::snapshot(sandbox)?;
+                match Sandbox::load_from(bytes.as_slice()) {
+                    Ok(snapshot) => *sandbox = snapshot,
+                    Err(err) => {
+                        tracing::error!("Failed to restore snapshot: {err}");
+                        return None;
+                    }
+                }
+                Some(current)
+            }
+        }
+    }
+
+    fn snapshot(sandbox: &Sandbox) -> Option<Self> {
+        let mut bytes = Vec::new();
+        match sandbox.save_to(&mut bytes) {
+            Ok(()) => Some(Self::Snapshot(bytes)),
+            Err(err) => {
+                tracing::error!("Failed to take snapshot: {err}");
+                None
+            }
+        }
+    }
+}
+
+/// Undo and redo of brush strokes and whole-world snapshots.
+#[derive(Default)]
+pub struct History {
+    undo: VecDeque<Edit>,
+    redo: Vec<Edit>,
+    /// Cells touched by the stroke in progress, with the values they had before it.
+    stroke: HashMap<(isize, isize), Cell>,
+    /// Whether the stroke in progress outgrew the budget and isn't recorded anymore.
+    stroke_dropped: bool,
+}
+
+impl History {
+    /// Remembers the cell before the current stroke changes it for the first time.
+    /// Strokes too large to ever fit the budget are dropped instead of undone partially.
+    pub fn record(&mut self, sandbox: &Sandbox, x: isize, y: isize) {
+        if self.stroke_dropped {
+            return;
+        }
+
+        if let Some(cell) = sandbox.get(x, y) {
+            self.stroke.entry((x, y)).or_insert(cell);
+        }
+
+        if self.stroke.len() * STROKE_CELL_SIZE > MEMORY_BUDGET {
+            tracing::info!("Stroke is too large to undo");
+            self.stroke = HashMap::new();
+            self.stroke_dropped = true;
+        }
+    }
+
+    pub fn finish_stroke(&mut self) {
+        if std::mem::take(&mut self.stroke_dropped) {
+            self.redo.clear();
+            return;
+        }
+
+        if self.stroke.is_empty() {
+            return;
+        }
+
+        let cells = self.stroke.drain().collect();
+        self.push(Edit::Stroke(cells));
+    }
+
+    /// Remembers the whole world, so the simulation running afterwards can be undone as well.
+    pub fn snapshot(&mut self, sandbox: &Sandbox) {
+        if let Some(snapshot) = Edit::snapshot(sandbox) {
+            self.push(snapshot);
+        }
+    }
+
+    /// Snapshots are only restored while paused, they'd throw away everything simulated since.
+    pub fn undo(&mut self, sandbox: &mut Sandbox, paused: bool) {
+        self.finish_stroke();
+        let Some(edit) = self.undo.pop_back() else {
+            return;
+        };
+
+        if matches!(edit, Edit::Snapshot(_)) && !paused {
+            tracing::info!("Pause to undo the simulation");
+            self.undo.push_back(edit);
+            return;
+        }
+
+        if let Some(redo) = edit.revert(sandbox) {
+            self.redo.push(redo);
+        }
+    }
+
+    pub fn redo(&mut self, sandbox: &mut Sandbox, paused: bool) {
+        self.finish_stroke();
+        let Some(edit) = self.redo.pop() else {
+            return;
+        };
+
+        if matches!(edit, Edit::Snapshot(_)) && !paused {
+            tracing::info!("Pause to redo the simulation");
+            self.redo.push(edit);
+            return;
+        }
+
+        if let Some(undo) = edit.revert(sandbox) {
+            self.undo.push_back(undo);
+        }
+    }
+
+    fn push(&mut self, edit: Edit) {
+        self.redo.clear();
+        if edit.size() > MEMORY_BUDGET {
+            tracing::info!("Edit is too large to undo");
+            return;
+        }
+        self.undo.push_back(edit);
+
+        let mut memory: usize = self.undo.iter().map(Edit::size).sum();
+        while memory > MEMORY_BUDGET {
+            if let Some(oldest) = self.undo.pop_front() {
+                memory -= oldest.size();
+            }
+        }
+    }
+}
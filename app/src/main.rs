@@ -1,24 +1,46 @@
 use crate::app::App;
 use lemon_sand_core::cell::Cell;
+use lemon_sand_core::image::{ColorMatching, Palette};
+use lemon_sand_core::material::MaterialRegistry;
+use lemon_sand_core::sandbox::Sandbox;
 use std::error::Error;
+use std::path::Path;
 use winit::event_loop::{ControlFlow, EventLoop};
 
 mod app;
+mod camera;
+mod clock;
+mod gui;
+mod history;
 
 const WIDTH: usize = 640;
 const HEIGHT: usize = 360;
+const MATERIALS_PATH: &str = "materials.toml";
+/// Imported images always start from the same seed, so they play out like in the CLI.
+const IMPORT_SEED: u64 = 0;
 
 fn main() -> Result<(), Box<dyn Error>> {
     tracing_subscriber::fmt()
         .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
         .init();
 
+    if Path::new(MATERIALS_PATH).exists() {
+        MaterialRegistry::load(MATERIALS_PATH)?.install()?;
+        tracing::info!("Loaded custom materials from {MATERIALS_PATH}");
+    }
+
     let event_loop = EventLoop::new()?;
     event_loop.set_control_flow(ControlFlow::Poll);
 
     let mut app = App::new(WIDTH, HEIGHT);
-    app.sandbox.place(50, 179, Cell::sand());
-    app.sandbox.place(50, 178, Cell::water());
+    if let Some(path) = std::env::args().nth(1) {
+        let palette = Palette::materials().with_matching(ColorMatching::Nearest);
+        app.sandbox = Sandbox::import_png(&path, &palette, IMPORT_SEED)?;
+        tracing::info!("Imported sandbox from {path}");
+    } else {
+        app.sandbox.place(50, 179, Cell::sand());
+        app.sandbox.place(50, 178, Cell::water());
+    }
     event_loop.run_app(&mut app)?;
 
     Ok(())
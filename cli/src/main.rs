@@ -0,0 +1,223 @@
+use clap::Parser;
+use lemon_sand_core::cell::CellType;
+use lemon_sand_core::image::{Animation, ColorMatching, FrameRecorder, GifRecorder, Palette};
+use lemon_sand_core::material::MaterialRegistry;
+use lemon_sand_core::sandbox::Sandbox;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+/// Seed of images simulated without `--seed`, so their runs can always be compared.
+const IMAGE_SEED: u64 = 0;
+
+/// Runs a sandbox without a window and writes the results.
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Args {
+    /// Save file or PNG image to start from.
+    input: PathBuf,
+    /// Ticks to simulate.
+    #[arg(short, long, default_value_t = 100)]
+    ticks: usize,
+    /// Seed for the simulation. Without one, saves keep their own seed and images use 0.
+    #[arg(short, long)]
+    seed: Option<u64>,
+    /// Custom material definitions, like the materials.toml read by the app.
+    #[arg(long)]
+    materials: Option<PathBuf>,
+    /// Require images to only use exact material colours instead of the nearest one.
+    #[arg(long)]
+    exact_colors: bool,
+    /// Where to save the final state.
+    #[arg(long)]
+    state: Option<PathBuf>,
+    /// Where to render the final state as a PNG.
+    #[arg(long)]
+    png: Option<PathBuf>,
+    /// Where to record the simulation as an animated GIF.
+    #[arg(long)]
+    gif: Option<PathBuf>,
+    /// Directory to record the simulation into as numbered PNG frames.
+    #[arg(long)]
+    frames: Option<PathBuf>,
+    /// Ticks between recorded frames, the first frame shows the state before simulating.
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    frame_every: u64,
+    /// Pixels per cell of the rendered PNG and recorded frames.
+    #[arg(long, default_value_t = 1)]
+    scale: usize,
+    /// Where to write statistics for every tick as CSV.
+    #[arg(long)]
+    stats: Option<PathBuf>,
+}
+
+fn main() -> ExitCode {
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .init();
+
+    // Printed by hand, returning the error from main would show its debug representation.
+    match run(&Args::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &Args) -> Result<(), Box<dyn Error>> {
+    if let Some(path) = &args.materials {
+        MaterialRegistry::load(path)?.install()?;
+        tracing::info!("Loaded custom materials from {}", path.display());
+    }
+
+    let mut sandbox = load_sandbox(args)?;
+    tracing::info!(
+        "Simulating {}x{} cells for {} ticks",
+        sandbox.width(),
+        sandbox.height(),
+        args.ticks
+    );
+
+    let mut stats = match &args.stats {
+        Some(path) => {
+            let mut writer = BufWriter::new(File::create(path)?);
+            write_stats_header(&mut writer)?;
+            Some(writer)
+        }
+        None => None,
+    };
+
+    let frame_every = args.frame_every as usize;
+    let animation = Animation {
+        frames: args.ticks / frame_every + 1,
+        ticks_per_frame: frame_every,
+        scale: args.scale,
+        ..Default::default()
+    };
+    let mut gif = match &args.gif {
+        Some(path) => Some(GifRecorder::new(
+            BufWriter::new(File::create(path)?),
+            &sandbox,
+            &animation,
+        )?),
+        None => None,
+    };
+    let mut frames = match &args.frames {
+        Some(directory) => Some(FrameRecorder::new(directory, &animation)?),
+        None => None,
+    };
+
+    for tick in 0..=args.ticks {
+        if tick > 0 {
+            sandbox.update();
+            if let Some(writer) = &mut stats {
+                write_stats(writer, tick, &sandbox)?;
+            }
+        }
+
+        if tick % frame_every == 0 {
+            if let Some(recorder) = &mut gif {
+                recorder.record(&sandbox)?;
+            }
+            if let Some(recorder) = &mut frames {
+                recorder.record(&sandbox)?;
+            }
+        }
+    }
+
+    if let Some(writer) = &mut stats {
+        writer.flush()?;
+    }
+
+    if let (Some(recorder), Some(path)) = (gif, &args.gif) {
+        recorder.finish()?.flush()?;
+        tracing::info!("Recorded GIF to {}", path.display());
+    }
+
+    if let (Some(recorder), Some(directory)) = (frames, &args.frames) {
+        let count = recorder.into_paths().len();
+        tracing::info!("Recorded {count} frames to {}", directory.display());
+    }
+
+    if let Some(path) = &args.state {
+        sandbox.save(path)?;
+        tracing::info!("Saved state to {}", path.display());
+    }
+
+    if let Some(path) = &args.png {
+        sandbox.export_png(path, args.scale)?;
+        tracing::info!("Rendered PNG to {}", path.display());
+    }
+
+    Ok(())
+}
+
+fn load_sandbox(args: &Args) -> Result<Sandbox, Box<dyn Error>> {
+    let is_image = args
+        .input
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+
+    if !is_image {
+        let mut sandbox = Sandbox::load(&args.input)?;
+        if let Some(seed) = args.seed {
+            sandbox.reseed(seed);
+        }
+        return Ok(sandbox);
+    }
+
+    let matching = if args.exact_colors {
+        ColorMatching::Exact
+    } else {
+        ColorMatching::Nearest
+    };
+    let palette = Palette::materials().with_matching(matching);
+    Ok(Sandbox::import_png(
+        &args.input,
+        &palette,
+        args.seed.unwrap_or(IMAGE_SEED),
+    )?)
+}
+
+fn write_stats_header(writer: &mut impl Write) -> std::io::Result<()> {
+    write!(writer, "tick,active_cells,total_moisture")?;
+    for (_, material) in MaterialRegistry::global().iter() {
+        write!(writer, ",{}", material.name)?;
+    }
+    writeln!(writer)
+}
+
+fn write_stats(writer: &mut impl Write, tick: usize, sandbox: &Sandbox) -> std::io::Result<()> {
+    let mut counts: HashMap<CellType, usize> = HashMap::new();
+    let mut active_cells = 0;
+    let mut total_moisture = 0.0f64;
+
+    for y in 0..sandbox.height() as isize {
+        for x in 0..sandbox.width() as isize {
+            let Some(cell) = sandbox.get(x, y) else {
+                continue;
+            };
+
+            *counts.entry(cell.get_type()).or_default() += 1;
+            total_moisture += cell.moisture as f64;
+            if sandbox.is_awake(x, y) {
+                active_cells += 1;
+            }
+        }
+    }
+
+    write!(writer, "{tick},{active_cells},{total_moisture:.3}")?;
+    for (type_, _) in MaterialRegistry::global().iter() {
+        write!(
+            writer,
+            ",{}",
+            counts.get(&type_).copied().unwrap_or_default()
+        )?;
+    }
+    writeln!(writer)
+}
@@ -0,0 +1,242 @@
+use crate::cell::{Cell, CellType};
+use crate::sandbox::Sandbox;
+use std::collections::HashSet;
+
+/// What a brush does to the cells it touches.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BrushAction {
+    Place(CellType),
+    Erase,
+    /// Adds the given degrees, negative values cool the cells down.
+    Heat(f32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Brush {
+    pub action: BrushAction,
+    /// Only cells of this material are affected, everything else is left alone.
+    pub replace_only: Option<CellType>,
+}
+
+impl Brush {
+    pub fn new(action: BrushAction) -> Self {
+        Self {
+            action,
+            replace_only: None,
+        }
+    }
+
+    /// Whether the brush would change the cell at the given position.
+    pub fn affects(&self, sandbox: &Sandbox, x: isize, y: isize) -> bool {
+        sandbox.get(x, y).is_some_and(|cell| {
+            self.replace_only
+                .is_none_or(|type_| cell.get_type() == type_)
+        })
+    }
+
+    pub fn apply(&self, sandbox: &mut Sandbox, x: isize, y: isize) {
+        if !self.affects(sandbox, x, y) {
+            return;
+        }
+
+        match self.action {
+            BrushAction::Place(type_) => sandbox.place(x, y, Cell::new(type_)),
+            BrushAction::Erase => sandbox.place(x, y, Cell::default()),
+            BrushAction::Heat(amount) => sandbox.heat(x, y, amount),
+        }
+    }
+
+    /// Applies the brush to all given cells, calling `before_change` right before any
+    /// of them is changed, e.g. to remember what it looked like.
+    pub fn paint(
+        &self,
+        sandbox: &mut Sandbox,
+        cells: impl IntoIterator<Item = (isize, isize)>,
+        mut before_change: impl FnMut(&Sandbox, isize, isize),
+    ) {
+        for (x, y) in cells {
+            if self.affects(sandbox, x, y) {
+                before_change(sandbox, x, y);
+                self.apply(sandbox, x, y);
+            }
+        }
+    }
+}
+
+/// Every cell within `radius` of the center.
+pub fn circle(center: (isize, isize), radius: usize) -> Vec<(isize, isize)> {
+    let r = radius as isize;
+    let mut cells = Vec::new();
+    for dy in -r..=r {
+        for dx in -r..=r {
+            if dx * dx + dy * dy <= r * r {
+                cells.push((center.0 + dx, center.1 + dy));
+            }
+        }
+    }
+    cells
+}
+
+/// The cells on the straight line between both points, including them.
+pub fn line(from: (isize, isize), to: (isize, isize)) -> Vec<(isize, isize)> {
+    let (dx, dy) = ((to.0 - from.0).abs(), -(to.1 - from.1).abs());
+    let (step_x, step_y) = ((to.0 - from.0).signum(), (to.1 - from.1).signum());
+    let mut error = dx + dy;
+    let mut current = from;
+    let mut cells = vec![current];
+
+    while current != to {
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            current.0 += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            current.1 += step_y;
+        }
+        cells.push(current);
+    }
+
+    cells
+}
+
+/// A circle of `radius` swept along the line between both points, without gaps or duplicates.
+pub fn stroke(from: (isize, isize), to: (isize, isize), radius: usize) -> Vec<(isize, isize)> {
+    let mut seen = HashSet::new();
+    line(from, to)
+        .into_iter()
+        .flat_map(|center| circle(center, radius))
+        .filter(|cell| seen.insert(*cell))
+        .collect()
+}
+
+/// Every cell of the rectangle spanned by both corners.
+pub fn rect(a: (isize, isize), b: (isize, isize)) -> Vec<(isize, isize)> {
+    let (min_x, max_x) = (a.0.min(b.0), a.0.max(b.0));
+    let (min_y, max_y) = (a.1.min(b.1), a.1.max(b.1));
+    (min_y..=max_y)
+        .flat_map(|y| (min_x..=max_x).map(move |x| (x, y)))
+        .collect()
+}
+
+/// The border of the rectangle spanned by both corners, `thickness` cells wide.
+pub fn hollow_rect(a: (isize, isize), b: (isize, isize), thickness: usize) -> Vec<(isize, isize)> {
+    let (min_x, max_x) = (a.0.min(b.0), a.0.max(b.0));
+    let (min_y, max_y) = (a.1.min(b.1), a.1.max(b.1));
+    let thickness = thickness.max(1) as isize;
+
+    rect(a, b)
+        .into_iter()
+        .filter(|&(x, y)| {
+            let border_distance = (x - min_x).min(max_x - x).min(y - min_y).min(max_y - y);
+            border_distance < thickness
+        })
+        .collect()
+}
+
+/// The connected area of cells sharing the material of the start cell, not counting diagonals.
+pub fn flood_fill(sandbox: &Sandbox, start: (isize, isize)) -> Vec<(isize, isize)> {
+    let Some(target) = sandbox.get(start.0, start.1).map(|cell| cell.get_type()) else {
+        return Vec::new();
+    };
+
+    let width = sandbox.width();
+    let mut visited = vec![false; width * sandbox.height()];
+    visited[start.1 as usize * width + start.0 as usize] = true;
+
+    let mut cells = Vec::new();
+    let mut pending = vec![start];
+    while let Some((x, y)) = pending.pop() {
+        cells.push((x, y));
+
+        for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+            let Some(neighbour) = sandbox.get(nx, ny) else {
+                continue;
+            };
+
+            let index = ny as usize * width + nx as usize;
+            if !visited[index] && neighbour.get_type() == target {
+                visited[index] = true;
+                pending.push((nx, ny));
+            }
+        }
+    }
+
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_include_both_ends_without_gaps() {
+        for to in [(7, 3), (-5, 9), (0, -6), (4, 4), (0, 0)] {
+            let cells = line((0, 0), to);
+            assert_eq!(cells.first(), Some(&(0, 0)));
+            assert_eq!(cells.last(), Some(&to));
+            assert_eq!(cells.len() as isize, to.0.abs().max(to.1.abs()) + 1);
+            for pair in cells.windows(2) {
+                let (a, b) = (pair[0], pair[1]);
+                assert!(
+                    (a.0 - b.0).abs() <= 1 && (a.1 - b.1).abs() <= 1,
+                    "{a:?} {b:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn strokes_have_no_duplicates() {
+        let cells = stroke((0, 0), (10, 4), 2);
+        let unique: HashSet<_> = cells.iter().collect();
+        assert_eq!(unique.len(), cells.len());
+        assert!(circle((10, 4), 2).iter().all(|cell| unique.contains(cell)));
+    }
+
+    #[test]
+    fn hollow_rects_are_as_thick_as_asked() {
+        let cells: HashSet<_> = hollow_rect((9, 9), (0, 0), 2).into_iter().collect();
+        assert_eq!(cells.len(), 100 - 36);
+        assert!(cells.contains(&(1, 5)) && cells.contains(&(8, 8)));
+        assert!(!cells.contains(&(2, 5)) && !cells.contains(&(7, 7)));
+
+        // Thicker than half the rectangle, it is filled completely.
+        assert_eq!(hollow_rect((0, 0), (3, 3), 5).len(), 16);
+    }
+
+    #[test]
+    fn flood_fill_stops_at_other_materials_and_diagonals() {
+        // A diagonal wall, its cells and the areas on both sides only touch diagonally.
+        let mut sandbox = Sandbox::with_seed(8, 8, 1);
+        for x in 0..8 {
+            sandbox.place(x, 7 - x, Cell::new(CellType::WALL));
+        }
+
+        let cells = flood_fill(&sandbox, (0, 0));
+        assert_eq!(cells.len(), 28);
+        assert!(cells.iter().all(|&(x, y)| x + y < 7));
+
+        assert_eq!(flood_fill(&sandbox, (7, 0)), [(7, 0)]);
+        assert!(flood_fill(&sandbox, (-1, 0)).is_empty());
+    }
+
+    #[test]
+    fn painting_reports_only_changed_cells() {
+        let mut sandbox = Sandbox::with_seed(8, 8, 1);
+        sandbox.place(1, 0, Cell::sand());
+        let brush = Brush {
+            action: BrushAction::Erase,
+            replace_only: Some(CellType::SAND),
+        };
+
+        let mut changed = Vec::new();
+        brush.paint(&mut sandbox, line((0, 0), (3, 0)), |sandbox, x, y| {
+            assert_eq!(sandbox.get(x, y).unwrap().get_type(), CellType::SAND);
+            changed.push((x, y));
+        });
+        assert_eq!(changed, [(1, 0)]);
+        assert!(sandbox.get(1, 0).unwrap().is_empty());
+    }
+}
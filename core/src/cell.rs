@@ -1,10 +1,31 @@
-#[derive(Debug, Default, Clone, Copy)]
+use crate::material::{Material, MaterialRegistry, PhaseTransition};
+use serde::Deserialize;
+use std::fmt;
+
+/// Default temperature of the surroundings in °C.
+pub const AMBIENT_TEMPERATURE: f32 = 20.0;
+
+#[derive(Debug, Clone, Copy)]
 pub struct Cell {
     type_: CellType,
     pub vx: f32,
     pub vy: f32,
     pub moisture: f32,
+    pub temperature: f32,
+    /// Heat absorbed (positive) or released (negative) by a phase transition in progress.
+    pub latent_heat: f32,
+    /// Ticks left to burn.
+    pub fuel: f32,
     pub last_updated: u8,
+    /// Tick the cell was last simulated at. Sleeping cells catch up on the exchange with
+    /// the surroundings they missed once they wake up again.
+    pub simulated_at: u32,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self::new(CellType::EMPTY)
+    }
 }
 
 impl Cell {
@@ -14,140 +35,166 @@ impl Cell {
             vx: 0.0,
             vy: 0.0,
             moisture: type_.inherent_wetness(),
+            temperature: type_.inherent_temperature(),
+            latent_heat: 0.0,
+            fuel: type_.inherent_fuel(),
             last_updated: 0,
+            simulated_at: 0,
         }
     }
 
+    /// The cell turned into another material, by a phase transition, burning up or the like.
+    /// Temperature and velocity carry over, moisture only as much as the new material can hold,
+    /// moisture sources start out full. Everything else, like fuel and latent heat, starts anew.
+    pub fn convert(&self, into: CellType, update_counter: u8) -> Cell {
+        let mut cell = Cell::new(into);
+        cell.vx = self.vx;
+        cell.vy = self.vy;
+        cell.temperature = self.temperature;
+        cell.simulated_at = self.simulated_at;
+        if !cell.is_pure_source(CellProperty::Moisture) {
+            cell.moisture = self
+                .moisture
+                .min(cell.property_capacity(CellProperty::Moisture));
+        }
+        cell.last_updated = update_counter;
+        cell
+    }
+
     pub fn get_type(&self) -> CellType {
         self.type_
     }
 
+    pub fn material(&self) -> &'static Material {
+        self.type_.material()
+    }
+
     pub fn color_rgba(&self) -> [u8; 4] {
-        match self.type_ {
-            CellType::Empty => [0, 0, 0, 255],
-
-            CellType::Sand => [
-                lerp_u8(
-                    245,
-                    195,
-                    self.moisture / self.property_capacity(CellProperty::Moisture),
-                ),
-                lerp_u8(
-                    237,
-                    174,
-                    self.moisture / self.property_capacity(CellProperty::Moisture),
-                ),
-                lerp_u8(
-                    190,
-                    142,
-                    self.moisture / self.property_capacity(CellProperty::Moisture),
-                ),
-                255,
-            ],
-            CellType::Water => [109, 109, 210, 255],
+        let material = self.material();
+        let [r, g, b] = material.color;
+
+        match &material.gradient {
+            Some(gradient) => {
+                let t = gradient.fraction(self);
+                let [tr, tg, tb] = gradient.color;
+                [lerp_u8(r, tr, t), lerp_u8(g, tg, t), lerp_u8(b, tb, t), 255]
+            }
+            None => [r, g, b, 255],
         }
     }
 
     pub fn movement(&self) -> CellMovement {
-        match self.get_type() {
-            CellType::Empty => CellMovement::None,
-            CellType::Sand => CellMovement::Powder,
-            CellType::Water => CellMovement::Liquid,
-        }
+        self.material().movement
     }
 
+    /// See [`Material::density`].
     pub fn density(&self) -> u8 {
-        match self.get_type() {
-            CellType::Empty => 0,
-            CellType::Sand => 10,
-            CellType::Water => 1,
-        }
+        self.material().density
     }
 
+    /// See [`Material::gravity_factor`].
     pub fn gravity_factor(&self) -> f32 {
-        match self.get_type() {
-            CellType::Empty => 0.0,
-            CellType::Sand => 1.0,
-            CellType::Water => 1.0,
-        }
+        self.material().gravity_factor
     }
 
     pub fn slide_speed_factor(&self) -> f32 {
-        match self.get_type() {
-            CellType::Empty => 0.0,
-            CellType::Sand => (0.8 - self.moisture * 0.6),
-            CellType::Water => 1.0,
-        }
+        let material = self.material();
+        material.slide_speed + self.moisture * material.slide_speed_per_moisture
     }
 
-    /// Horizontal impulse when blocked (liquids seeking level)
+    /// See [`Material::spread_impulse`], none once wetter than [`Material::spread_max_moisture`].
     pub fn spread_impulse(&self) -> f32 {
-        match self.get_type() {
-            CellType::Empty => 0.0,
-            CellType::Sand => {
-                if self.moisture > 0.2 {
-                    0.0
-                } else {
-                    0.01
-                }
-            }
-            CellType::Water => 5.0,
+        let material = self.material();
+        if self.moisture > material.spread_max_moisture {
+            0.0
+        } else {
+            material.spread_impulse
         }
     }
 
+    /// Wet powders cling to their neighbours instead of falling.
+    pub fn is_cohesive(&self) -> bool {
+        self.moisture >= self.material().cohesive_moisture
+    }
+
+    /// See [`Material::drift_impulse`].
+    pub fn drift_impulse(&self) -> f32 {
+        self.material().drift_impulse
+    }
+
+    /// See [`Material::drag`].
+    pub fn drag(&self) -> f32 {
+        self.material().drag
+    }
+
+    /// See [`Material::viscosity`].
+    pub fn viscosity(&self) -> f32 {
+        self.material().viscosity
+    }
+
+    /// See [`Material::dissipation_chance`].
+    pub fn dissipation_chance(&self) -> f32 {
+        self.material().dissipation_chance
+    }
+
     pub fn get_property(&self, property: CellProperty) -> f32 {
         match property {
             CellProperty::Moisture => self.moisture,
+            CellProperty::Temperature => self.temperature,
         }
     }
 
     pub fn set_property(&mut self, property: CellProperty, value: f32) {
         match property {
             CellProperty::Moisture => self.moisture = value,
+            CellProperty::Temperature => self.temperature = value,
         }
     }
 
     /// How much of a property a cell can hold.
+    /// For temperature this is the heat capacity, the energy needed per degree.
     pub fn property_capacity(&self, property: CellProperty) -> f32 {
+        let material = self.material();
         match property {
-            CellProperty::Moisture => match self.get_type() {
-                CellType::Empty => 0.0,
-                CellType::Sand => 1.5,
-                CellType::Water => 0.0,
-            },
+            CellProperty::Moisture => material.moisture.capacity,
+            CellProperty::Temperature => material.temperature.capacity,
         }
     }
 
     /// How much of a property a cell wants to hold before propagating excess.
     pub fn property_min_saturation(&self, property: CellProperty) -> f32 {
         match property {
-            CellProperty::Moisture => match self.get_type() {
-                CellType::Empty => 0.0,
-                CellType::Sand => 0.5,
-                CellType::Water => 0.0,
-            },
+            CellProperty::Moisture => self.material().moisture.min_saturation,
+            CellProperty::Temperature => f32::NEG_INFINITY,
         }
     }
 
     /// How fast the property can propagate out of a cell.
+    /// For temperature this is the thermal conductivity (0.0 - 1.0).
     pub fn property_diffusion_rate(&self, property: CellProperty) -> f32 {
+        let material = self.material();
         match property {
-            CellProperty::Moisture => match self.get_type() {
-                CellType::Empty => 0.0,
-                CellType::Sand => 0.01,
-                CellType::Water => 1.0,
-            },
+            CellProperty::Moisture => material.moisture.diffusion_rate,
+            CellProperty::Temperature => material.temperature.conductivity,
         }
     }
 
     /// How fast the property can propagate into a cell.
+    /// For temperature this is the thermal conductivity (0.0 - 1.0).
     pub fn property_accept_rate(&self, property: CellProperty) -> f32 {
+        let material = self.material();
+        match property {
+            CellProperty::Moisture => material.moisture.accept_rate,
+            CellProperty::Temperature => material.temperature.conductivity,
+        }
+    }
+
+    /// Fraction of the difference to the surrounding ambient value that is exchanged per tick.
+    pub fn property_ambient_exchange(&self, property: CellProperty) -> f32 {
+        let material = self.material();
         match property {
-            CellProperty::Moisture => match self.get_type() {
-                CellType::Empty => 0.0,
-                CellType::Sand => 0.05,
-                CellType::Water => 0.0,
-            },
+            CellProperty::Moisture => material.moisture.ambient_exchange,
+            CellProperty::Temperature => material.temperature.ambient_exchange,
         }
     }
 
@@ -163,7 +210,7 @@ impl Cell {
         if value > diffusion_rate {
             diffusion_rate
         } else {
-            self.moisture
+            value
         }
     }
 
@@ -184,48 +231,198 @@ impl Cell {
         }
     }
 
+    /// Whether the property spreads from this cell into the other one. Pure sources hand it to
+    /// everything taking it, other cells only to those holding clearly less of it, smaller
+    /// differences would only make neighbours hand the same amount back and forth.
+    pub fn spreads_to(&self, other: &Cell, property: CellProperty) -> bool {
+        other.property_accept_potential(property) > 0.0
+            && (!other.is_empty() && self.is_pure_source(property)
+                || other.get_property(property) + 2.0 * self.property_diffuse_potential(property)
+                    < self.get_property(property))
+    }
+
     pub fn is_pure_source(&self, property: CellProperty) -> bool {
         match property {
-            CellProperty::Moisture => matches!(self.get_type(), CellType::Water),
+            CellProperty::Moisture => self.material().moisture.pure_source,
+            CellProperty::Temperature => false,
         }
     }
 
+    /// Burning cells and those hot enough to act like them on their neighbours.
+    pub fn is_scorching(&self) -> bool {
+        self.is_burning() || self.temperature >= self.material().scorch_temperature
+    }
+
+    pub fn is_burning(&self) -> bool {
+        self.material()
+            .combustion
+            .as_ref()
+            .is_some_and(|combustion| {
+                self.fuel > 0.0
+                    && self.temperature >= combustion.ignition_temperature
+                    && self.moisture <= combustion.max_moisture
+            })
+    }
+
+    /// Whether the cell could be set alight right now.
+    pub fn is_flammable(&self) -> bool {
+        self.material()
+            .combustion
+            .as_ref()
+            .is_some_and(|combustion| self.fuel > 0.0 && self.moisture <= combustion.max_moisture)
+    }
+
     pub fn is_empty(&self) -> bool {
-        matches!(self.get_type(), CellType::Empty)
+        self.get_type() == CellType::EMPTY
+    }
+
+    pub fn is_buoyant(&self) -> bool {
+        self.gravity_factor() < 0.0
+    }
+
+    /// Immovable solids, nothing can displace or push them.
+    pub fn is_static(&self) -> bool {
+        !self.is_empty() && matches!(self.movement(), CellMovement::None)
+    }
+
+    /// Whether the cell can be pushed aside by rising gas.
+    pub fn is_fluid(&self) -> bool {
+        self.is_empty() || matches!(self.movement(), CellMovement::Liquid | CellMovement::Gas)
+    }
+
+    pub fn is_liquid(&self) -> bool {
+        !self.is_empty() && matches!(self.movement(), CellMovement::Liquid)
     }
 
     pub fn is_water(&self) -> bool {
-        matches!(self.get_type(), CellType::Water)
+        self.get_type() == CellType::WATER
     }
 
     pub fn sand() -> Self {
-        Self::new(CellType::Sand)
+        Self::new(CellType::SAND)
     }
 
     pub fn water() -> Self {
-        Self::new(CellType::Water)
+        Self::new(CellType::WATER)
     }
-}
 
-#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Hash)]
-pub enum CellType {
-    #[default]
-    Empty,
-    Sand,
-    Water,
+    pub fn smoke() -> Self {
+        Self::new(CellType::SMOKE)
+    }
+
+    pub fn steam() -> Self {
+        Self::new(CellType::STEAM)
+    }
+
+    pub fn ice() -> Self {
+        Self::new(CellType::ICE)
+    }
+
+    pub fn snow() -> Self {
+        Self::new(CellType::SNOW)
+    }
+
+    pub fn fire() -> Self {
+        Self::new(CellType::FIRE)
+    }
 }
 
+/// Compact id of a material in the [`MaterialRegistry`].
+#[derive(Default, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct CellType(pub(crate) u16);
+
 impl CellType {
+    pub const EMPTY: Self = Self(0);
+    pub const SAND: Self = Self(1);
+    pub const WATER: Self = Self(2);
+    pub const SMOKE: Self = Self(3);
+    pub const STEAM: Self = Self(4);
+    pub const ICE: Self = Self(5);
+    pub const GLASS: Self = Self(6);
+    pub const FIRE: Self = Self(7);
+    pub const WOOD: Self = Self(8);
+    pub const PLANT: Self = Self(9);
+    pub const OIL: Self = Self(10);
+    pub const STONE: Self = Self(11);
+    pub const WALL: Self = Self(12);
+    pub const METAL: Self = Self(13);
+    pub const MERCURY: Self = Self(14);
+    pub const HONEY: Self = Self(15);
+    pub const LAVA: Self = Self(16);
+    pub const SNOW: Self = Self(17);
+    pub const MUD: Self = Self(18);
+
+    pub(crate) const BUILTIN: [(&'static str, Self); 19] = [
+        ("empty", Self::EMPTY),
+        ("sand", Self::SAND),
+        ("water", Self::WATER),
+        ("smoke", Self::SMOKE),
+        ("steam", Self::STEAM),
+        ("ice", Self::ICE),
+        ("glass", Self::GLASS),
+        ("fire", Self::FIRE),
+        ("wood", Self::WOOD),
+        ("plant", Self::PLANT),
+        ("oil", Self::OIL),
+        ("stone", Self::STONE),
+        ("wall", Self::WALL),
+        ("metal", Self::METAL),
+        ("mercury", Self::MERCURY),
+        ("honey", Self::HONEY),
+        ("lava", Self::LAVA),
+        ("snow", Self::SNOW),
+        ("mud", Self::MUD),
+    ];
+
+    pub fn material(&self) -> &'static Material {
+        MaterialRegistry::global().get(*self)
+    }
+
+    pub fn name(&self) -> &'static str {
+        &self.material().name
+    }
+
     pub fn inherent_wetness(&self) -> f32 {
-        match self {
-            CellType::Empty => 0.0,
-            CellType::Sand => 0.0,
-            CellType::Water => 1.0,
+        self.material().moisture.inherent
+    }
+
+    pub fn inherent_temperature(&self) -> f32 {
+        self.material().temperature.inherent
+    }
+
+    pub fn inherent_fuel(&self) -> f32 {
+        self.material()
+            .combustion
+            .as_ref()
+            .map_or(0.0, |combustion| combustion.fuel)
+    }
+
+    /// Phase transitions of this type, checked every update.
+    /// Cells stay at the threshold until they absorbed (or released) the latent heat,
+    /// so they don't flicker between states while hovering around it.
+    pub fn transitions(&self) -> &'static [PhaseTransition] {
+        &self.material().transitions
+    }
+}
+
+impl fmt::Debug for CellType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if (self.0 as usize) < MaterialRegistry::global().len() {
+            f.write_str(self.name())
+        } else {
+            write!(f, "CellType({})", self.0)
         }
     }
 }
 
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Clone, Copy)]
+pub enum Threshold {
+    Above(f32),
+    Below(f32),
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum CellMovement {
     #[default]
     None,
@@ -234,11 +431,34 @@ pub enum CellMovement {
     Gas,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum CellProperty {
     Moisture,
+    Temperature,
 }
 
 fn lerp_u8(a: u8, b: u8, t: f32) -> u8 {
     (a as f32 + (b as f32 - a as f32) * t.clamp(0.0, 1.0)) as u8
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversions_keep_only_moisture_the_material_can_hold() {
+        let mut sand = Cell::sand();
+        sand.moisture = 1.46;
+        sand.temperature = 35.0;
+        sand.fuel = 3.0;
+        assert_eq!(sand.convert(CellType::MUD, 1).moisture, 1.46);
+        assert_eq!(sand.convert(CellType::GLASS, 1).moisture, 0.0);
+
+        let water = sand.convert(CellType::WATER, 1);
+        assert_eq!(water.moisture, CellType::WATER.inherent_wetness());
+        assert_eq!(water.temperature, 35.0);
+        assert_eq!(water.fuel, 0.0);
+        assert_eq!(water.last_updated, 1);
+    }
+}
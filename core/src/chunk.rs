@@ -0,0 +1,183 @@
+/// Edge length of the square chunks the sandbox is split into.
+pub const CHUNK_SIZE: usize = 32;
+/// Cells around a chunk that are copied along with it when updating in parallel.
+/// Nothing a cell does in one tick may reach further than this.
+pub(crate) const HALO: usize = CHUNK_SIZE / 2;
+
+/// Inclusive rectangle of cells as its top left and bottom right corner.
+pub(crate) type CellRect = ((usize, usize), (usize, usize));
+
+/// Inclusive rectangle of cells, in sandbox coordinates.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct DirtyRect {
+    min_x: usize,
+    min_y: usize,
+    max_x: usize,
+    max_y: usize,
+}
+
+impl DirtyRect {
+    const EMPTY: Self = Self {
+        min_x: usize::MAX,
+        min_y: usize::MAX,
+        max_x: 0,
+        max_y: 0,
+    };
+
+    fn is_empty(&self) -> bool {
+        self.min_x > self.max_x
+    }
+
+    fn include(&mut self, min: (usize, usize), max: (usize, usize)) {
+        self.min_x = self.min_x.min(min.0);
+        self.min_y = self.min_y.min(min.1);
+        self.max_x = self.max_x.max(max.0);
+        self.max_y = self.max_y.max(max.1);
+    }
+
+    fn contains(&self, x: usize, y: usize) -> bool {
+        (self.min_x..=self.max_x).contains(&x) && (self.min_y..=self.max_y).contains(&y)
+    }
+}
+
+/// Tracks which part of every chunk needs to be updated.
+/// Cells are only simulated inside the dirty rect their chunk collected during the previous tick,
+/// chunks without any activity are skipped entirely.
+pub(crate) struct Chunks {
+    width: usize,
+    height: usize,
+    chunks_x: usize,
+    chunks_y: usize,
+    current: Vec<DirtyRect>,
+    next: Vec<DirtyRect>,
+}
+
+impl Chunks {
+    pub fn new(width: usize, height: usize) -> Self {
+        let chunks_x = width.div_ceil(CHUNK_SIZE);
+        let chunks_y = height.div_ceil(CHUNK_SIZE);
+
+        let mut chunks = Self {
+            width,
+            height,
+            chunks_x,
+            chunks_y,
+            current: vec![DirtyRect::EMPTY; chunks_x * chunks_y],
+            next: vec![DirtyRect::EMPTY; chunks_x * chunks_y],
+        };
+        chunks.wake_all();
+        chunks
+    }
+
+    pub fn chunks_x(&self) -> usize {
+        self.chunks_x
+    }
+
+    #[cfg(feature = "parallel")]
+    pub fn chunks_y(&self) -> usize {
+        self.chunks_y
+    }
+
+    /// Whether any cell of the chunk is being simulated this tick.
+    #[cfg(feature = "parallel")]
+    pub fn is_chunk_awake(&self, cx: usize, cy: usize) -> bool {
+        !self.current[cy * self.chunks_x + cx].is_empty()
+    }
+
+    /// Makes the activity collected during the last tick the work of the upcoming one.
+    pub fn advance(&mut self) {
+        std::mem::swap(&mut self.current, &mut self.next);
+        self.next.fill(DirtyRect::EMPTY);
+    }
+
+    pub fn wake_all(&mut self) {
+        for cy in 0..self.chunks_y {
+            for cx in 0..self.chunks_x {
+                let (min, max) = self.bounds(cx, cy);
+                self.next[cy * self.chunks_x + cx].include(min, max);
+            }
+        }
+    }
+
+    /// The areas woken up for the next tick, one inclusive rectangle per chunk
+    /// with `None` for chunks that are going to sleep.
+    pub fn pending(&self) -> impl Iterator<Item = Option<CellRect>> {
+        self.next.iter().map(|rect| {
+            (!rect.is_empty()).then_some(((rect.min_x, rect.min_y), (rect.max_x, rect.max_y)))
+        })
+    }
+
+    /// Replaces the areas woken up for the next tick with ones taken from [`Chunks::pending`].
+    /// Wakes everything instead if they don't fit the chunks of this tracker.
+    pub fn restore_pending(&mut self, pending: &[Option<CellRect>]) {
+        let fits = pending.len() == self.next.len()
+            && pending.iter().flatten().all(|&(min, max)| {
+                min.0 <= max.0 && min.1 <= max.1 && max.0 < self.width && max.1 < self.height
+            });
+        if !fits {
+            self.wake_all();
+            return;
+        }
+
+        self.next.fill(DirtyRect::EMPTY);
+        for &(min, max) in pending.iter().flatten() {
+            self.include(min, max);
+        }
+    }
+
+    /// Wakes the cell and its direct neighbours for the next tick, across chunk borders.
+    pub fn mark(&mut self, x: usize, y: usize) {
+        let min = (x.saturating_sub(1), y.saturating_sub(1));
+        let max = ((x + 1).min(self.width - 1), (y + 1).min(self.height - 1));
+        self.include(min, max);
+    }
+
+    /// Adds the activity another tracker collected for the next tick,
+    /// with `origin` being the position of its top left cell in this one.
+    #[cfg(feature = "parallel")]
+    pub fn merge(&mut self, other: &Chunks, origin: (usize, usize)) {
+        for rect in other.next.iter().filter(|rect| !rect.is_empty()) {
+            self.include(
+                (origin.0 + rect.min_x, origin.1 + rect.min_y),
+                (origin.0 + rect.max_x, origin.1 + rect.max_y),
+            );
+        }
+    }
+
+    /// Wakes the inclusive rectangle for the next tick, across chunk borders.
+    fn include(&mut self, min: (usize, usize), max: (usize, usize)) {
+        for cy in min.1 / CHUNK_SIZE..=max.1 / CHUNK_SIZE {
+            for cx in min.0 / CHUNK_SIZE..=max.0 / CHUNK_SIZE {
+                let (chunk_min, chunk_max) = self.bounds(cx, cy);
+                self.next[cy * self.chunks_x + cx].include(
+                    (min.0.max(chunk_min.0), min.1.max(chunk_min.1)),
+                    (max.0.min(chunk_max.0), max.1.min(chunk_max.1)),
+                );
+            }
+        }
+    }
+
+    /// The inclusive span of cells to update in the given row of a chunk this tick.
+    pub fn row_span(&self, cx: usize, y: usize) -> Option<(usize, usize)> {
+        let rect = &self.current[(y / CHUNK_SIZE) * self.chunks_x + cx];
+        if rect.is_empty() || !(rect.min_y..=rect.max_y).contains(&y) {
+            return None;
+        }
+        Some((rect.min_x, rect.max_x))
+    }
+
+    /// Whether the cell is being simulated this tick.
+    pub fn is_awake(&self, x: usize, y: usize) -> bool {
+        self.current[(y / CHUNK_SIZE) * self.chunks_x + x / CHUNK_SIZE].contains(x, y)
+    }
+
+    /// The inclusive cell rectangle covered by the chunk.
+    pub fn bounds(&self, cx: usize, cy: usize) -> ((usize, usize), (usize, usize)) {
+        let min = (cx * CHUNK_SIZE, cy * CHUNK_SIZE);
+        let max = (
+            (min.0 + CHUNK_SIZE).min(self.width) - 1,
+            (min.1 + CHUNK_SIZE).min(self.height) - 1,
+        );
+        (min, max)
+    }
+}
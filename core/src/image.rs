@@ -0,0 +1,447 @@
+use crate::cell::{Cell, CellType};
+use crate::material::MaterialRegistry;
+use crate::sandbox::Sandbox;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Seek, Write};
+use std::path::{Path, PathBuf};
+
+/// Quality of the colour quantization for GIF frames, from 1 (best) to 30 (fastest).
+const GIF_QUANTIZATION_SPEED: i32 = 10;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImageError {
+    #[error("failed to access image: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to decode image: {0}")]
+    Decode(#[from] png::DecodingError),
+    #[error("failed to encode PNG: {0}")]
+    EncodePng(#[from] png::EncodingError),
+    #[error("failed to encode GIF: {0}")]
+    EncodeGif(#[from] gif::EncodingError),
+    #[error("image of {width}x{height} pixels is too large")]
+    TooLarge { width: usize, height: usize },
+    #[error("color #{:02x}{:02x}{:02x} at {x} {y} is not in the palette", color[0], color[1], color[2])]
+    UnknownColor { x: usize, y: usize, color: [u8; 3] },
+}
+
+/// How pixel colours are matched against the palette.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMatching {
+    /// Every opaque pixel has to use a palette colour exactly.
+    #[default]
+    Exact,
+    /// Pixels become the material with the closest palette colour.
+    Nearest,
+}
+
+/// Maps pixel colours to materials when importing images.
+#[derive(Debug, Clone)]
+pub struct Palette {
+    entries: Vec<([u8; 3], CellType)>,
+    matching: ColorMatching,
+    /// Pixels less opaque than this become empty, regardless of their colour.
+    alpha_threshold: u8,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            matching: ColorMatching::default(),
+            alpha_threshold: 128,
+        }
+    }
+}
+
+impl Palette {
+    /// A palette of every registered material, using the colour a freshly placed cell is drawn with.
+    /// Materials sharing a colour resolve to the first one registered.
+    pub fn materials() -> Self {
+        MaterialRegistry::global()
+            .iter()
+            .fold(Self::default(), |palette, (type_, _)| {
+                let [r, g, b, _] = Cell::new(type_).color_rgba();
+                palette.with_color([r, g, b], type_)
+            })
+    }
+
+    /// Adds a colour, replacing the material of an existing entry with the same colour.
+    pub fn with_color(mut self, color: [u8; 3], type_: CellType) -> Self {
+        match self.entries.iter_mut().find(|(c, _)| *c == color) {
+            Some(entry) => entry.1 = type_,
+            None => self.entries.push((color, type_)),
+        }
+        self
+    }
+
+    pub fn with_matching(mut self, matching: ColorMatching) -> Self {
+        self.matching = matching;
+        self
+    }
+
+    pub fn with_alpha_threshold(mut self, alpha_threshold: u8) -> Self {
+        self.alpha_threshold = alpha_threshold;
+        self
+    }
+
+    /// The material the given pixel turns into, if the palette has one for it.
+    pub fn get(&self, [r, g, b, a]: [u8; 4]) -> Option<CellType> {
+        if a < self.alpha_threshold {
+            return Some(CellType::EMPTY);
+        }
+
+        match self.matching {
+            ColorMatching::Exact => self
+                .entries
+                .iter()
+                .find(|(color, _)| *color == [r, g, b])
+                .map(|(_, type_)| *type_),
+            ColorMatching::Nearest => self
+                .entries
+                .iter()
+                .min_by_key(|([cr, cg, cb], _)| {
+                    let distance = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+                    distance(r, *cr) + distance(g, *cg) + distance(b, *cb)
+                })
+                .map(|(_, type_)| *type_),
+        }
+    }
+}
+
+/// How to export the simulation over time.
+#[derive(Debug, Clone, Copy)]
+pub struct Animation {
+    /// Frames exported in total, the first one shows the sandbox before simulating.
+    pub frames: usize,
+    pub ticks_per_frame: usize,
+    /// Pixels per cell in both directions.
+    pub scale: usize,
+    /// Time between frames of animated GIFs, in hundredths of a second.
+    pub frame_delay: u16,
+}
+
+impl Default for Animation {
+    fn default() -> Self {
+        Self {
+            frames: 60,
+            ticks_per_frame: 1,
+            scale: 1,
+            frame_delay: 2,
+        }
+    }
+}
+
+/// Writes the frames it is given as numbered PNGs into a directory,
+/// for simulations driven by the caller instead of [`Sandbox::export_frames`].
+/// Only the scale of the [`Animation`] is used.
+#[derive(Debug)]
+pub struct FrameRecorder {
+    directory: PathBuf,
+    scale: usize,
+    paths: Vec<PathBuf>,
+}
+
+impl FrameRecorder {
+    pub fn new(directory: impl AsRef<Path>, animation: &Animation) -> Result<Self, ImageError> {
+        std::fs::create_dir_all(&directory)?;
+        Ok(Self {
+            directory: directory.as_ref().to_path_buf(),
+            scale: animation.scale,
+            paths: Vec::new(),
+        })
+    }
+
+    pub fn record(&mut self, sandbox: &Sandbox) -> Result<(), ImageError> {
+        let index = self.paths.len();
+        let path = self.directory.join(format!("frame_{index:05}.png"));
+        sandbox.export_png(&path, self.scale)?;
+        self.paths.push(path);
+        Ok(())
+    }
+
+    /// The paths of the frames written so far.
+    pub fn into_paths(self) -> Vec<PathBuf> {
+        self.paths
+    }
+}
+
+/// Writes the frames it is given into a looping animated GIF,
+/// for simulations driven by the caller instead of [`Sandbox::export_gif`].
+/// Only the scale and frame delay of the [`Animation`] are used.
+pub struct GifRecorder<W: Write> {
+    encoder: gif::Encoder<W>,
+    width: u16,
+    height: u16,
+    scale: usize,
+    frame_delay: u16,
+}
+
+impl<W: Write> GifRecorder<W> {
+    /// Every recorded sandbox has to have the size of the given one.
+    pub fn new(writer: W, sandbox: &Sandbox, animation: &Animation) -> Result<Self, ImageError> {
+        let scale = animation.scale.max(1);
+        let (width, height) = (sandbox.width() * scale, sandbox.height() * scale);
+        let too_large = || ImageError::TooLarge { width, height };
+        let (width, height): (u16, u16) = (
+            width.try_into().map_err(|_| too_large())?,
+            height.try_into().map_err(|_| too_large())?,
+        );
+
+        let mut encoder = gif::Encoder::new(writer, width, height, &[])?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+
+        Ok(Self {
+            encoder,
+            width,
+            height,
+            scale,
+            frame_delay: animation.frame_delay,
+        })
+    }
+
+    pub fn record(&mut self, sandbox: &Sandbox) -> Result<(), ImageError> {
+        let (_, _, mut pixels) = sandbox.render(self.scale);
+        let mut frame = gif::Frame::from_rgba_speed(
+            self.width,
+            self.height,
+            &mut pixels,
+            GIF_QUANTIZATION_SPEED,
+        );
+        frame.delay = self.frame_delay;
+        self.encoder.write_frame(&frame)?;
+        Ok(())
+    }
+
+    /// Ends the GIF and returns the writer it was written to.
+    pub fn finish(self) -> Result<W, ImageError> {
+        Ok(self.encoder.into_inner()?)
+    }
+}
+
+impl Sandbox {
+    /// Renders the sandbox into a new RGBA buffer, with every cell being `scale` pixels wide and tall.
+    /// Returns the width and height in pixels along with the buffer.
+    pub fn render(&self, scale: usize) -> (usize, usize, Vec<u8>) {
+        let scale = scale.max(1);
+        let mut frame = vec![0; self.width() * self.height() * 4];
+        self.draw(&mut frame);
+
+        if scale == 1 {
+            return (self.width(), self.height(), frame);
+        }
+
+        let (width, height) = (self.width() * scale, self.height() * scale);
+        let mut scaled = Vec::with_capacity(width * height * 4);
+        for row in frame.chunks_exact(self.width() * 4) {
+            let start = scaled.len();
+            for pixel in row.chunks_exact(4) {
+                for _ in 0..scale {
+                    scaled.extend_from_slice(pixel);
+                }
+            }
+            for _ in 1..scale {
+                scaled.extend_from_within(start..start + width * 4);
+            }
+        }
+
+        (width, height, scaled)
+    }
+
+    pub fn export_png(&self, path: impl AsRef<Path>, scale: usize) -> Result<(), ImageError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.export_png_to(&mut writer, scale)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn export_png_to(&self, writer: impl Write, scale: usize) -> Result<(), ImageError> {
+        let (width, height, pixels) = self.render(scale);
+        let too_large = || ImageError::TooLarge { width, height };
+
+        let mut encoder = png::Encoder::new(
+            writer,
+            width.try_into().map_err(|_| too_large())?,
+            height.try_into().map_err(|_| too_large())?,
+        );
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&pixels)?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Simulates the sandbox while writing every frame as a numbered PNG into the directory.
+    /// Returns the paths of the written frames.
+    pub fn export_frames(
+        &mut self,
+        directory: impl AsRef<Path>,
+        animation: &Animation,
+    ) -> Result<Vec<PathBuf>, ImageError> {
+        let mut recorder = FrameRecorder::new(directory, animation)?;
+        self.animate(animation, |sandbox| recorder.record(sandbox))?;
+        Ok(recorder.into_paths())
+    }
+
+    pub fn export_gif(
+        &mut self,
+        path: impl AsRef<Path>,
+        animation: &Animation,
+    ) -> Result<(), ImageError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.export_gif_to(&mut writer, animation)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Simulates the sandbox while recording it as a looping animated GIF.
+    pub fn export_gif_to(
+        &mut self,
+        writer: impl Write,
+        animation: &Animation,
+    ) -> Result<(), ImageError> {
+        let mut recorder = GifRecorder::new(writer, self, animation)?;
+        self.animate(animation, |sandbox| recorder.record(sandbox))?;
+        recorder.finish()?;
+        Ok(())
+    }
+
+    /// Calls `on_frame` for every frame of the animation, simulating in between.
+    fn animate(
+        &mut self,
+        animation: &Animation,
+        mut on_frame: impl FnMut(&Self) -> Result<(), ImageError>,
+    ) -> Result<(), ImageError> {
+        for index in 0..animation.frames {
+            if index > 0 {
+                for _ in 0..animation.ticks_per_frame {
+                    self.update();
+                }
+            }
+            on_frame(self)?;
+        }
+        Ok(())
+    }
+
+    pub fn import_png(
+        path: impl AsRef<Path>,
+        palette: &Palette,
+        seed: u64,
+    ) -> Result<Self, ImageError> {
+        Self::import_png_from(BufReader::new(File::open(path)?), palette, seed)
+    }
+
+    /// Builds a sandbox the size of the image, with one cell per pixel.
+    /// Like [`Sandbox::with_seed`], the same image and seed always play out the same way.
+    pub fn import_png_from(
+        reader: impl BufRead + Seek,
+        palette: &Palette,
+        seed: u64,
+    ) -> Result<Self, ImageError> {
+        let mut decoder = png::Decoder::new(reader);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+
+        let mut buffer = vec![0; reader.output_buffer_size().unwrap_or_default()];
+        let info = reader.next_frame(&mut buffer)?;
+        let (width, height) = (info.width as usize, info.height as usize);
+        let samples = info.color_type.samples();
+
+        let mut sandbox = Sandbox::with_seed(width, height, seed);
+        for y in 0..height {
+            let row = &buffer[y * info.line_size..(y + 1) * info.line_size];
+            for x in 0..width {
+                // Indexed and 16 bit images are expanded to 8 bit samples by the transformations above.
+                let pixel = &row[x * samples..(x + 1) * samples];
+                let rgba = match *pixel {
+                    [l] => [l, l, l, 255],
+                    [l, a] => [l, l, l, a],
+                    [r, g, b] => [r, g, b, 255],
+                    [r, g, b, a] => [r, g, b, a],
+                    _ => unreachable!("pixels have at most four samples"),
+                };
+
+                let type_ = palette.get(rgba).ok_or(ImageError::UnknownColor {
+                    x,
+                    y,
+                    color: [rgba[0], rgba[1], rgba[2]],
+                })?;
+
+                if type_ != CellType::EMPTY {
+                    sandbox.place(x as isize, y as isize, Cell::new(type_));
+                }
+            }
+        }
+
+        Ok(sandbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn sample() -> Sandbox {
+        let mut sandbox = Sandbox::with_seed(24, 16, 3);
+        for x in 4..20 {
+            sandbox.place(x, 15, Cell::new(CellType::WALL));
+            sandbox.place(x, 2, Cell::sand());
+            sandbox.place(x, 4, Cell::water());
+        }
+        sandbox
+    }
+
+    fn png(sandbox: &Sandbox, scale: usize) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        sandbox.export_png_to(&mut bytes, scale).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn imports_with_the_same_seed_play_out_the_same() {
+        let image = png(&sample(), 1);
+        let import = || {
+            let mut sandbox =
+                Sandbox::import_png_from(Cursor::new(&image), &Palette::materials(), 5).unwrap();
+            for _ in 0..40 {
+                sandbox.update();
+            }
+            png(&sandbox, 1)
+        };
+
+        assert_eq!(import(), import());
+    }
+
+    #[test]
+    fn exports_are_scaled() {
+        let sandbox = sample();
+        let image = png(&sandbox, 3);
+        let mut decoder = png::Decoder::new(Cursor::new(&image)).read_info().unwrap();
+        let mut buffer = vec![0; decoder.output_buffer_size().unwrap()];
+        let info = decoder.next_frame(&mut buffer).unwrap();
+        assert_eq!((info.width, info.height), (72, 48));
+
+        let animation = Animation {
+            frames: 5,
+            ticks_per_frame: 2,
+            scale: 2,
+            ..Default::default()
+        };
+        let mut gif = Vec::new();
+        sample().export_gif_to(&mut gif, &animation).unwrap();
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(Cursor::new(&gif)).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (48, 32));
+
+        let mut frames = 0;
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            assert_eq!((frame.width, frame.height), (48, 32));
+            frames += 1;
+        }
+        assert_eq!(frames, 5);
+    }
+}
@@ -1,2 +1,7 @@
+pub mod brush;
 pub mod cell;
+pub mod chunk;
+pub mod image;
+pub mod material;
+pub mod reaction;
 pub mod sandbox;
@@ -0,0 +1,654 @@
+use crate::cell::{AMBIENT_TEMPERATURE, Cell, CellMovement, CellProperty, CellType, Threshold};
+use crate::chunk::HALO;
+use crate::reaction::{ReactionDefinition, ReactionTable};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::OnceLock;
+
+const BUILTIN_MATERIALS: &str = include_str!("../materials.toml");
+
+static REGISTRY: OnceLock<MaterialRegistry> = OnceLock::new();
+
+#[derive(Debug, thiserror::Error)]
+pub enum MaterialError {
+    #[error("failed to read material file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to parse material file: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("material '{material}' references unknown material '{reference}'")]
+    UnknownMaterial { material: String, reference: String },
+    #[error("reaction '{reaction}' references unknown material '{reference}'")]
+    UnknownReactionMaterial { reaction: String, reference: String },
+    #[error("transition of material '{0}' needs exactly one of 'above' or 'below'")]
+    InvalidTransition(String),
+    #[error("compaction load of material '{material}' is {load}, at most {HALO} is supported")]
+    CompactionLoadTooLarge { material: String, load: usize },
+    #[error("too many materials, at most {} are supported", u16::MAX)]
+    TooManyMaterials,
+    #[error("a material registry is already installed")]
+    AlreadyInstalled,
+}
+
+/// All materials cells can be made of, indexed by [`CellType`], and the reactions between them.
+#[derive(Debug)]
+pub struct MaterialRegistry {
+    materials: Vec<Material>,
+    names: HashMap<String, CellType>,
+    reactions: ReactionTable,
+}
+
+impl MaterialRegistry {
+    /// The materials shipped with the core crate.
+    pub fn builtin() -> Self {
+        let registry = Self::from_definitions(MaterialFile::default(), BUILTIN_MATERIALS)
+            .expect("built-in materials are valid");
+
+        for (name, type_) in CellType::BUILTIN {
+            assert_eq!(
+                registry.find(name),
+                Some(type_),
+                "built-in material '{name}' is out of order"
+            );
+        }
+
+        registry
+    }
+
+    /// The built-in materials extended by the given TOML definitions.
+    /// Definitions named like an existing material replace it, reactions are always added.
+    pub fn from_toml(source: &str) -> Result<Self, MaterialError> {
+        let builtin: MaterialFile = toml::from_str(BUILTIN_MATERIALS)?;
+        Self::from_definitions(builtin, source)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MaterialError> {
+        Self::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    /// Makes this registry the one used by all cells.
+    /// Has to happen before any cell is created, since they'd otherwise use the built-in materials.
+    pub fn install(self) -> Result<(), MaterialError> {
+        REGISTRY
+            .set(self)
+            .map_err(|_| MaterialError::AlreadyInstalled)
+    }
+
+    pub fn global() -> &'static Self {
+        REGISTRY.get_or_init(Self::builtin)
+    }
+
+    pub fn get(&self, type_: CellType) -> &Material {
+        &self.materials[type_.0 as usize]
+    }
+
+    pub fn find(&self, name: &str) -> Option<CellType> {
+        self.names.get(name).copied()
+    }
+
+    pub fn reactions(&self) -> &ReactionTable {
+        &self.reactions
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (CellType, &Material)> {
+        self.materials
+            .iter()
+            .enumerate()
+            .map(|(i, material)| (CellType(i as u16), material))
+    }
+
+    pub fn len(&self) -> usize {
+        self.materials.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.materials.is_empty()
+    }
+
+    fn from_definitions(base: MaterialFile, source: &str) -> Result<Self, MaterialError> {
+        let file: MaterialFile = toml::from_str(source)?;
+        let mut definitions = base.material;
+        let reactions = base.reaction.into_iter().chain(file.reaction);
+
+        for definition in file.material {
+            match definitions.iter_mut().find(|d| d.name == definition.name) {
+                Some(existing) => *existing = definition,
+                None => definitions.push(definition),
+            }
+        }
+
+        if definitions.len() > u16::MAX as usize {
+            return Err(MaterialError::TooManyMaterials);
+        }
+
+        let names: HashMap<String, CellType> = definitions
+            .iter()
+            .enumerate()
+            .map(|(i, definition)| (definition.name.clone(), CellType(i as u16)))
+            .collect();
+
+        let mut materials: Vec<Material> = definitions
+            .into_iter()
+            .map(|definition| definition.resolve(&names))
+            .collect::<Result<_, _>>()?;
+
+        let mut table = ReactionTable::default();
+        for reaction in reactions {
+            let reaction = reaction.resolve(&names)?;
+            for reactant in reaction.reactants {
+                materials[reactant.0 as usize].reactive = true;
+            }
+            table.insert(reaction);
+        }
+
+        Ok(Self {
+            materials,
+            names,
+            reactions: table,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Material {
+    pub name: String,
+    pub color: [u8; 3],
+    pub gradient: Option<Gradient>,
+    pub movement: CellMovement,
+    /// Gases are lighter than empty space (air), everything else is heavier.
+    pub density: u8,
+    /// Negative values make a cell buoyant.
+    pub gravity_factor: f32,
+    pub slide_speed: f32,
+    /// Change of the slide speed per unit of moisture.
+    pub slide_speed_per_moisture: f32,
+    /// Horizontal impulse when blocked (liquids seeking level)
+    pub spread_impulse: f32,
+    /// Moisture above which the spread impulse no longer applies.
+    pub spread_max_moisture: f32,
+    /// Moisture from which on powders cling to their neighbours, forming walls and overhangs.
+    pub cohesive_moisture: f32,
+    /// Temperature from which on the cell boils off touching water and sets flammable
+    /// neighbours alight, just like a burning one.
+    pub scorch_temperature: f32,
+    /// Random horizontal impulse applied every tick (gases diffusing)
+    pub drift_impulse: f32,
+    /// Fraction of velocity lost every tick to air resistance.
+    pub drag: f32,
+    /// Resistance to flowing sideways (0.0 - 1.0), damps horizontal velocity and
+    /// is the chance to not spread at all in a tick.
+    pub viscosity: f32,
+    /// Chance per tick that the cell disappears.
+    pub dissipation_chance: f32,
+    pub moisture: MoistureParams,
+    pub temperature: ThermalParams,
+    /// Phase transitions, checked every update.
+    pub transitions: Vec<PhaseTransition>,
+    /// Materials the cell turns into once it gets too wet or dry, keeping its moisture.
+    pub moisture_transitions: Vec<MoistureTransition>,
+    /// What a moisture source turns into when its moisture boils away.
+    pub vapor: Option<CellType>,
+    pub combustion: Option<Combustion>,
+    pub compaction: Option<Compaction>,
+    /// Whether any reaction involves this material.
+    pub reactive: bool,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MoistureParams {
+    pub inherent: f32,
+    /// How much moisture a cell can hold.
+    pub capacity: f32,
+    /// How much moisture a cell wants to hold before propagating excess.
+    pub min_saturation: f32,
+    /// How fast moisture can propagate out of a cell.
+    pub diffusion_rate: f32,
+    /// How fast moisture can propagate into a cell.
+    pub accept_rate: f32,
+    /// Fraction of the moisture lost to the surroundings per tick.
+    pub ambient_exchange: f32,
+    /// Sources hand out moisture to everything and vanish once depleted.
+    pub pure_source: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThermalParams {
+    pub inherent: f32,
+    /// Energy needed to change the temperature by one degree.
+    pub capacity: f32,
+    /// How easily heat flows in and out of a cell (0.0 - 1.0).
+    pub conductivity: f32,
+    /// Fraction of the difference to the ambient temperature that is exchanged per tick.
+    pub ambient_exchange: f32,
+}
+
+impl Default for ThermalParams {
+    fn default() -> Self {
+        Self {
+            inherent: AMBIENT_TEMPERATURE,
+            capacity: 1.0,
+            conductivity: 0.0,
+            ambient_exchange: 0.0,
+        }
+    }
+}
+
+/// Blends the base color towards `color` as the property rises within `range`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Gradient {
+    pub property: CellProperty,
+    pub color: [u8; 3],
+    /// Defaults to zero up to the capacity of the property.
+    pub range: Option<[f32; 2]>,
+    /// Random variation of the blend, changing along with the property to make cells shimmer.
+    #[serde(default)]
+    pub flicker: f32,
+}
+
+impl Gradient {
+    pub fn fraction(&self, cell: &Cell) -> f32 {
+        let [from, to] = self
+            .range
+            .unwrap_or([0.0, cell.property_capacity(self.property)]);
+        if to <= from {
+            return 0.0;
+        }
+
+        let value = cell.get_property(self.property);
+        let fraction = (value - from) / (to - from);
+        if self.flicker <= 0.0 {
+            return fraction;
+        }
+
+        // Mixes the bits of the value, which changes a tiny bit every tick while it's hot.
+        let mut bits = value.to_bits();
+        bits ^= bits >> 15;
+        bits = bits.wrapping_mul(0x2C1B_3C6D);
+        bits ^= bits >> 12;
+        let noise = (bits & 0xFFFF) as f32 / 0xFFFF as f32 * 2.0 - 1.0;
+        fraction + noise * self.flicker
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PhaseTransition {
+    pub threshold: Threshold,
+    /// Degrees beyond the threshold a cell has to absorb before it changes.
+    pub latent_heat: f32,
+    pub into: CellType,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MoistureTransition {
+    pub threshold: Threshold,
+    pub into: CellType,
+}
+
+/// How a flammable material burns.
+/// A cell burns while it is at least at its ignition temperature, dry enough and has fuel left.
+#[derive(Debug, Clone)]
+pub struct Combustion {
+    pub ignition_temperature: f32,
+    /// Chance per tick to be set alight by a burning neighbour.
+    pub flammability: f32,
+    /// Ticks the cell can burn.
+    pub fuel: f32,
+    /// Degrees gained per tick while burning, up to the burn temperature.
+    pub heat: f32,
+    pub burn_temperature: f32,
+    /// Cells holding more moisture than this can't burn.
+    pub max_moisture: f32,
+    /// Chance per tick to emit a flame into an empty neighbour.
+    pub flame_chance: f32,
+    /// Chance per tick to emit smoke into an empty neighbour.
+    pub smoke_chance: f32,
+    /// What is left once the fuel is used up.
+    pub burns_into: CellType,
+}
+
+/// How a material turns into another one under the weight of the cells resting on it.
+#[derive(Debug, Clone)]
+pub struct Compaction {
+    /// Cells stacked directly above it needed to compact, static ones carry their own weight.
+    /// At most half a chunk.
+    pub load: usize,
+    /// Chance per tick to compact while under enough load.
+    pub chance: f32,
+    pub into: CellType,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MaterialFile {
+    #[serde(default)]
+    material: Vec<MaterialDefinition>,
+    #[serde(default)]
+    reaction: Vec<ReactionDefinition>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MaterialDefinition {
+    name: String,
+    color: [u8; 3],
+    #[serde(default)]
+    gradient: Option<Gradient>,
+    #[serde(default)]
+    movement: CellMovement,
+    #[serde(default)]
+    density: u8,
+    #[serde(default)]
+    gravity_factor: f32,
+    #[serde(default)]
+    slide_speed: f32,
+    #[serde(default)]
+    slide_speed_per_moisture: f32,
+    #[serde(default)]
+    spread_impulse: f32,
+    #[serde(default = "unlimited")]
+    spread_max_moisture: f32,
+    #[serde(default = "unlimited")]
+    cohesive_moisture: f32,
+    #[serde(default = "unlimited")]
+    scorch_temperature: f32,
+    #[serde(default)]
+    drift_impulse: f32,
+    #[serde(default)]
+    drag: f32,
+    #[serde(default)]
+    viscosity: f32,
+    #[serde(default)]
+    dissipation_chance: f32,
+    #[serde(default)]
+    moisture: MoistureParams,
+    #[serde(default)]
+    temperature: ThermalParams,
+    #[serde(default)]
+    transitions: Vec<TransitionDefinition>,
+    #[serde(default)]
+    moisture_transitions: Vec<MoistureTransitionDefinition>,
+    #[serde(default)]
+    vapor: Option<String>,
+    #[serde(default)]
+    combustion: Option<CombustionDefinition>,
+    #[serde(default)]
+    compaction: Option<CompactionDefinition>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TransitionDefinition {
+    above: Option<f32>,
+    below: Option<f32>,
+    latent_heat: f32,
+    into: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MoistureTransitionDefinition {
+    above: Option<f32>,
+    below: Option<f32>,
+    into: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CombustionDefinition {
+    ignition_temperature: f32,
+    flammability: f32,
+    fuel: f32,
+    heat: f32,
+    burn_temperature: f32,
+    #[serde(default = "default_max_moisture")]
+    max_moisture: f32,
+    #[serde(default)]
+    flame_chance: f32,
+    #[serde(default)]
+    smoke_chance: f32,
+    #[serde(default = "default_residue")]
+    burns_into: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CompactionDefinition {
+    load: usize,
+    chance: f32,
+    into: String,
+}
+
+impl MaterialDefinition {
+    /// Transitions happen either above or below their threshold, never both.
+    fn threshold(
+        &self,
+        above: Option<f32>,
+        below: Option<f32>,
+    ) -> Result<Threshold, MaterialError> {
+        match (above, below) {
+            (Some(above), None) => Ok(Threshold::Above(above)),
+            (None, Some(below)) => Ok(Threshold::Below(below)),
+            _ => Err(MaterialError::InvalidTransition(self.name.clone())),
+        }
+    }
+
+    fn resolve(self, names: &HashMap<String, CellType>) -> Result<Material, MaterialError> {
+        let lookup = |reference: &String| {
+            names
+                .get(reference)
+                .copied()
+                .ok_or_else(|| MaterialError::UnknownMaterial {
+                    material: self.name.clone(),
+                    reference: reference.clone(),
+                })
+        };
+
+        let transitions = self
+            .transitions
+            .iter()
+            .map(|transition| {
+                Ok(PhaseTransition {
+                    threshold: self.threshold(transition.above, transition.below)?,
+                    latent_heat: transition.latent_heat,
+                    into: lookup(&transition.into)?,
+                })
+            })
+            .collect::<Result<_, MaterialError>>()?;
+
+        let moisture_transitions = self
+            .moisture_transitions
+            .iter()
+            .map(|transition| {
+                Ok(MoistureTransition {
+                    threshold: self.threshold(transition.above, transition.below)?,
+                    into: lookup(&transition.into)?,
+                })
+            })
+            .collect::<Result<_, MaterialError>>()?;
+
+        let vapor = self.vapor.as_ref().map(&lookup).transpose()?;
+        let combustion = match &self.combustion {
+            Some(combustion) => Some(Combustion {
+                ignition_temperature: combustion.ignition_temperature,
+                flammability: combustion.flammability,
+                fuel: combustion.fuel,
+                heat: combustion.heat,
+                burn_temperature: combustion.burn_temperature,
+                max_moisture: combustion.max_moisture,
+                flame_chance: combustion.flame_chance,
+                smoke_chance: combustion.smoke_chance,
+                burns_into: lookup(&combustion.burns_into)?,
+            }),
+            None => None,
+        };
+        let compaction = match &self.compaction {
+            // Cells further up than the halo are out of sight of the parallel update.
+            Some(compaction) if compaction.load > HALO => {
+                return Err(MaterialError::CompactionLoadTooLarge {
+                    material: self.name.clone(),
+                    load: compaction.load,
+                });
+            }
+            Some(compaction) => Some(Compaction {
+                load: compaction.load,
+                chance: compaction.chance,
+                into: lookup(&compaction.into)?,
+            }),
+            None => None,
+        };
+
+        Ok(Material {
+            name: self.name,
+            color: self.color,
+            gradient: self.gradient,
+            movement: self.movement,
+            density: self.density,
+            gravity_factor: self.gravity_factor,
+            slide_speed: self.slide_speed,
+            slide_speed_per_moisture: self.slide_speed_per_moisture,
+            spread_impulse: self.spread_impulse,
+            spread_max_moisture: self.spread_max_moisture,
+            cohesive_moisture: self.cohesive_moisture,
+            scorch_temperature: self.scorch_temperature,
+            drift_impulse: self.drift_impulse,
+            drag: self.drag,
+            viscosity: self.viscosity,
+            dissipation_chance: self.dissipation_chance,
+            moisture: self.moisture,
+            temperature: self.temperature,
+            transitions,
+            moisture_transitions,
+            vapor,
+            combustion,
+            compaction,
+            reactive: false,
+        })
+    }
+}
+
+fn unlimited() -> f32 {
+    f32::INFINITY
+}
+
+fn default_max_moisture() -> f32 {
+    0.2
+}
+
+fn default_residue() -> String {
+    "empty".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_materials_match_their_ids() {
+        let registry = MaterialRegistry::builtin();
+        assert_eq!(registry.len(), CellType::BUILTIN.len());
+        for (name, type_) in CellType::BUILTIN {
+            assert_eq!(registry.get(type_).name, name);
+        }
+    }
+
+    #[test]
+    fn custom_materials_are_appended_or_replace_by_name() {
+        let registry = MaterialRegistry::from_toml(
+            r#"
+            [[material]]
+            name = "sand"
+            color = [1, 2, 3]
+
+            [[material]]
+            name = "salt"
+            color = [250, 250, 250]
+            movement = "powder"
+            density = 22
+            "#,
+        )
+        .unwrap();
+
+        let builtin = CellType::BUILTIN.len();
+        assert_eq!(registry.len(), builtin + 1);
+        assert_eq!(registry.find("sand"), Some(CellType::SAND));
+        assert_eq!(registry.get(CellType::SAND).color, [1, 2, 3]);
+        assert_eq!(registry.find("salt"), Some(CellType(builtin as u16)));
+        assert_eq!(registry.get(CellType(builtin as u16)).density, 22);
+    }
+
+    #[test]
+    fn unknown_references_are_rejected() {
+        let material = MaterialRegistry::from_toml(
+            r#"
+            [[material]]
+            name = "salt"
+            color = [250, 250, 250]
+            vapor = "brine"
+            "#,
+        );
+        assert!(matches!(
+            material,
+            Err(MaterialError::UnknownMaterial { material, reference })
+                if material == "salt" && reference == "brine"
+        ));
+
+        let reaction = MaterialRegistry::from_toml(
+            r#"
+            [[reaction]]
+            reactants = ["sand", "brine"]
+            products = ["sand", "water"]
+            probability = 0.1
+            "#,
+        );
+        assert!(matches!(
+            reaction,
+            Err(MaterialError::UnknownReactionMaterial { reference, .. }) if reference == "brine"
+        ));
+    }
+
+    #[test]
+    fn transitions_need_exactly_one_threshold() {
+        for threshold in ["", "above = 10.0\nbelow = 0.0"] {
+            let result = MaterialRegistry::from_toml(&format!(
+                r#"
+                [[material]]
+                name = "salt"
+                color = [250, 250, 250]
+
+                [[material.transitions]]
+                {threshold}
+                latent_heat = 10.0
+                into = "water"
+                "#
+            ));
+            assert!(matches!(
+                result,
+                Err(MaterialError::InvalidTransition(name)) if name == "salt"
+            ));
+        }
+    }
+
+    #[test]
+    fn compaction_load_is_limited_to_the_halo() {
+        let result = MaterialRegistry::from_toml(&format!(
+            r#"
+            [[material]]
+            name = "salt"
+            color = [250, 250, 250]
+
+            [material.compaction]
+            load = {}
+            chance = 0.1
+            into = "stone"
+            "#,
+            HALO + 1
+        ));
+        assert!(matches!(
+            result,
+            Err(MaterialError::CompactionLoadTooLarge { load, .. }) if load == HALO + 1
+        ));
+    }
+}
@@ -0,0 +1,175 @@
+use crate::cell::{Cell, CellType};
+use crate::material::MaterialError;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Two neighbouring cells turning into new materials.
+/// Always seen from the perspective of the updating cell, which is the first participant.
+#[derive(Debug, Clone)]
+pub struct Reaction {
+    pub reactants: [CellType; 2],
+    pub products: [CellType; 2],
+    /// Chance that the reaction happens once all conditions are met, rolled whenever a
+    /// participant picks the other one as the neighbour to react with. Cells pick one of
+    /// their eight neighbours per tick and both sides can start the reaction, so touching
+    /// cells react with about a quarter of this chance per tick.
+    pub probability: f32,
+    pub conditions: [ReactionCondition; 2],
+    /// Degrees added to both products on top of the temperature of the reactants,
+    /// negative values absorb heat.
+    pub heat: f32,
+}
+
+impl Reaction {
+    pub fn is_possible(&self, first: &Cell, second: &Cell) -> bool {
+        self.conditions[0].is_met(first) && self.conditions[1].is_met(second)
+    }
+
+    /// Creates the product replacing the given participant, [`Self::heat`] is added on top
+    /// of the participant's temperature. Participants that stay the same material keep their
+    /// state, others are converted with [`Cell::convert`].
+    pub fn product(&self, index: usize, participant: Cell, update_counter: u8) -> Cell {
+        let product = self.products[index];
+        let mut cell = if participant.get_type() == product {
+            participant
+        } else {
+            participant.convert(product, update_counter)
+        };
+        cell.last_updated = update_counter;
+
+        cell.temperature += self.heat;
+        cell
+    }
+
+    fn mirrored(&self) -> Self {
+        let [a, b] = self.reactants;
+        let [into_a, into_b] = self.products;
+        let [condition_a, condition_b] = self.conditions.clone();
+
+        Self {
+            reactants: [b, a],
+            products: [into_b, into_a],
+            probability: self.probability,
+            conditions: [condition_b, condition_a],
+            heat: self.heat,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReactionCondition {
+    pub min_temperature: Option<f32>,
+    pub max_temperature: Option<f32>,
+    pub min_moisture: Option<f32>,
+    pub max_moisture: Option<f32>,
+}
+
+impl ReactionCondition {
+    pub fn is_met(&self, cell: &Cell) -> bool {
+        self.min_temperature
+            .is_none_or(|min| cell.temperature >= min)
+            && self
+                .max_temperature
+                .is_none_or(|max| cell.temperature <= max)
+            && self.min_moisture.is_none_or(|min| cell.moisture >= min)
+            && self.max_moisture.is_none_or(|max| cell.moisture <= max)
+    }
+}
+
+/// Reactions indexed by the pair of materials involved, in both orders.
+#[derive(Debug, Default)]
+pub struct ReactionTable {
+    reactions: HashMap<(CellType, CellType), Vec<Reaction>>,
+}
+
+impl ReactionTable {
+    pub fn get(&self, first: CellType, second: CellType) -> &[Reaction] {
+        self.reactions
+            .get(&(first, second))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn insert(&mut self, reaction: Reaction) {
+        let [a, b] = reaction.reactants;
+        if a != b {
+            self.reactions
+                .entry((b, a))
+                .or_default()
+                .push(reaction.mirrored());
+        }
+
+        self.reactions.entry((a, b)).or_default().push(reaction);
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ReactionDefinition {
+    reactants: [String; 2],
+    products: [String; 2],
+    probability: f32,
+    #[serde(default)]
+    conditions: [ReactionCondition; 2],
+    #[serde(default)]
+    heat: f32,
+}
+
+impl ReactionDefinition {
+    pub(crate) fn resolve(
+        self,
+        names: &HashMap<String, CellType>,
+    ) -> Result<Reaction, MaterialError> {
+        let lookup = |name: &String| {
+            names
+                .get(name)
+                .copied()
+                .ok_or_else(|| MaterialError::UnknownReactionMaterial {
+                    reaction: format!("{} + {}", self.reactants[0], self.reactants[1]),
+                    reference: name.clone(),
+                })
+        };
+
+        Ok(Reaction {
+            reactants: [lookup(&self.reactants[0])?, lookup(&self.reactants[1])?],
+            products: [lookup(&self.products[0])?, lookup(&self.products[1])?],
+            probability: self.probability,
+            conditions: self.conditions,
+            heat: self.heat,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn products_keep_the_state_of_their_participants() {
+        let reaction = Reaction {
+            reactants: [CellType::LAVA, CellType::WATER],
+            products: [CellType::STONE, CellType::WATER],
+            probability: 1.0,
+            conditions: Default::default(),
+            heat: 50.0,
+        };
+
+        let mut lava = Cell::new(CellType::LAVA);
+        lava.vy = 2.0;
+        let mut water = Cell::water();
+        water.moisture = 0.5;
+
+        let stone = reaction.product(0, lava, 1);
+        assert_eq!(stone.get_type(), CellType::STONE);
+        assert_eq!(stone.temperature, lava.temperature + 50.0);
+        assert_eq!(stone.vy, 2.0);
+
+        let water = reaction.product(1, water, 1);
+        assert_eq!(water.moisture, 0.5);
+        assert_eq!(
+            water.temperature,
+            CellType::WATER.inherent_temperature() + 50.0
+        );
+    }
+}
@@ -1,4 +1,45 @@
-use crate::cell::{Cell, CellMovement, CellProperty};
+use crate::cell::{AMBIENT_TEMPERATURE, Cell, CellMovement, CellProperty, CellType, Threshold};
+use crate::chunk::Chunks;
+#[cfg(feature = "parallel")]
+use crate::chunk::HALO;
+use crate::material::MaterialRegistry;
+use fastrand::Rng;
+
+mod render;
+mod save;
+
+pub use render::{OUTSIDE_COLOR, RenderMode};
+pub use save::{FORMAT_VERSION, SaveError};
+
+/// Pure moisture sources holding less than this are used up.
+const DEPLETION_THRESHOLD: f32 = 0.05;
+/// Temperature above which moisture held by cells boils off.
+const BOILING_POINT: f32 = 100.0;
+/// Moisture boiled off per tick and the degrees it takes with it per unit.
+const EVAPORATION_RATE: f32 = 0.02;
+const EVAPORATION_COOLING: f32 = 200.0;
+/// Moisture a burning cell boils off a touching moisture source and the degrees it loses doing so.
+const QUENCH_MOISTURE: f32 = 0.2;
+const QUENCH_COOLING: f32 = 150.0;
+/// Non-fluid neighbours a cohesive cell needs to hold on to, and the speed above which it can't.
+const COHESION_NEIGHBOURS: usize = 2;
+const COHESION_MAX_SPEED: f32 = 1.0;
+/// Distance up to which cohesive cells hold on to each other. Checking them mustn't reach
+/// beyond the halo of the parallel update.
+const COHESION_REACH: usize = 10;
+/// Smallest change of a property that keeps a cell's chunk awake.
+const ACTIVITY_THRESHOLD: f32 = 0.01;
+
+const NEIGHBOURS: [(isize, isize); 8] = [
+    (0, 1),
+    (-1, 1),
+    (1, 1),
+    (-1, 0),
+    (1, 0),
+    (0, -1),
+    (-1, -1),
+    (1, -1),
+];
 
 pub struct Sandbox {
     cells: Vec<Cell>,
@@ -6,21 +47,42 @@ pub struct Sandbox {
     height: usize,
     gravity: f32,
     max_velocity: f32,
+    ambient_temperature: f32,
     update_counter: u8,
+    /// Ticks simulated so far, wrapping around.
+    ticks: u32,
+    chunks: Chunks,
+    rng: Rng,
 }
 
 impl Sandbox {
+    /// Creates an empty sandbox with a random seed.
     pub fn new(width: usize, height: usize) -> Self {
+        Self::with_seed(width, height, fastrand::u64(..))
+    }
+
+    /// Creates an empty sandbox whose simulation only depends on the given seed,
+    /// the same seed and the same edits always produce the same frames.
+    pub fn with_seed(width: usize, height: usize, seed: u64) -> Self {
         Self {
             cells: vec![Cell::default(); width * height],
             width,
             height,
             gravity: 0.3,
             max_velocity: 8.0,
+            ambient_temperature: AMBIENT_TEMPERATURE,
             update_counter: 0,
+            ticks: 0,
+            chunks: Chunks::new(width, height),
+            rng: Rng::with_seed(seed),
         }
     }
 
+    /// Restarts the random number generator, e.g. to replay a loaded save with a different seed.
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = Rng::with_seed(seed);
+    }
+
     pub fn height(&self) -> usize {
         self.height
     }
@@ -29,6 +91,21 @@ impl Sandbox {
         self.width
     }
 
+    pub fn ambient_temperature(&self) -> f32 {
+        self.ambient_temperature
+    }
+
+    pub fn set_ambient_temperature(&mut self, temperature: f32) {
+        self.ambient_temperature = temperature;
+        self.chunks.wake_all();
+    }
+
+    /// Whether the cell at the given position was simulated during the last update,
+    /// cells in settled areas are skipped until something near them changes.
+    pub fn is_awake(&self, x: isize, y: isize) -> bool {
+        self.coords_to_index(x, y).is_some() && self.chunks.is_awake(x as usize, y as usize)
+    }
+
     fn coords_to_index(&self, x: isize, y: isize) -> Option<usize> {
         if x < 0 || y < 0 || x >= self.width as isize || y >= self.height as isize {
             return None;
@@ -48,7 +125,33 @@ impl Sandbox {
         let Some(to) = self.get(to.0, to.1) else {
             return false;
         };
-        cell.density() > to.density()
+
+        if to.is_static() {
+            return false;
+        }
+
+        if cell.is_buoyant() {
+            to.is_fluid() && cell.density() < to.density()
+        } else {
+            cell.density() > to.density()
+        }
+    }
+
+    /// Like [`Self::can_displace`], but liquids only sink through other liquids by chance,
+    /// the heavier and runnier they are in comparison the likelier.
+    fn try_displace(&mut self, cell: Cell, to: (isize, isize)) -> bool {
+        if !self.can_displace(cell, to) {
+            return false;
+        }
+
+        match self.get(to.0, to.1) {
+            Some(other) if cell.is_liquid() && other.is_liquid() => {
+                let (density, other_density) = (cell.density() as f32, other.density() as f32);
+                let chance = (density - other_density) / density * (1.0 - cell.viscosity());
+                self.rng.f32() < chance
+            }
+            _ => true,
+        }
     }
 
     fn swap_cells(&mut self, from: (isize, isize), to: (isize, isize)) {
@@ -63,33 +166,406 @@ impl Sandbox {
         self.cells[i].last_updated = self.update_counter;
         self.cells[j].last_updated = self.update_counter;
         self.cells.swap(i, j);
+        self.mark_active(from);
+        self.mark_active(to);
+    }
+
+    fn mark_active(&mut self, (x, y): (isize, isize)) {
+        if self.coords_to_index(x, y).is_some() {
+            self.chunks.mark(x as usize, y as usize);
+        }
     }
 
     #[tracing::instrument(skip_all)]
     pub fn update(&mut self) {
         self.update_counter = self.update_counter.wrapping_add(1);
-        for y in (0..self.height as isize).rev() {
-            let scan_right = fastrand::bool();
-            for i in 0..self.width as isize {
-                let x = if scan_right {
-                    i
-                } else {
-                    (self.width as isize - 1) - i
+        self.ticks = self.ticks.wrapping_add(1);
+        self.chunks.advance();
+
+        #[cfg(not(feature = "parallel"))]
+        self.update_rows();
+        #[cfg(feature = "parallel")]
+        self.update_checkerboard();
+    }
+
+    /// Also compiled for tests of the parallel build, so both updates can be compared.
+    #[cfg(any(test, not(feature = "parallel")))]
+    fn update_rows(&mut self) {
+        let chunks_x = self.chunks.chunks_x();
+        for y in (0..self.height).rev() {
+            let scan_right = self.rng.bool();
+            for i in 0..chunks_x {
+                let cx = if scan_right { i } else { chunks_x - 1 - i };
+                let Some((min_x, max_x)) = self.chunks.row_span(cx, y) else {
+                    continue;
                 };
-                self.update_cell(x, y);
+
+                for j in 0..=(max_x - min_x) {
+                    let x = if scan_right { min_x + j } else { max_x - j };
+                    self.update_tracked(x as isize, y as isize);
+                }
             }
         }
     }
 
+    /// Updates the chunks in four passes, so that chunks updated at the same time are
+    /// always a whole chunk apart. Every chunk is simulated on a copy of itself plus
+    /// [`HALO`] cells in every direction, which is further than any cell can reach in one tick.
+    /// The copies don't overlap and are written back in a fixed order afterwards.
+    #[cfg(feature = "parallel")]
+    fn update_checkerboard(&mut self) {
+        use rayon::prelude::*;
+
+        debug_assert!(self.max_velocity as usize + 2 <= HALO);
+
+        let mut passes = [(0, 0), (1, 0), (0, 1), (1, 1)];
+        self.rng.shuffle(&mut passes);
+
+        for (offset_x, offset_y) in passes {
+            // Every chunk gets its own generator seeded up front,
+            // so the outcome doesn't depend on how the work is scheduled.
+            let jobs: Vec<(usize, usize, u64)> = (offset_y..self.chunks.chunks_y())
+                .step_by(2)
+                .flat_map(|cy| {
+                    (offset_x..self.chunks.chunks_x())
+                        .step_by(2)
+                        .map(move |cx| (cx, cy))
+                })
+                .filter(|&(cx, cy)| self.chunks.is_chunk_awake(cx, cy))
+                .map(|(cx, cy)| (cx, cy, self.rng.u64(..)))
+                .collect();
+
+            let windows: Vec<((usize, usize), Sandbox)> = jobs
+                .par_iter()
+                .map(|&(cx, cy, seed)| self.update_window(cx, cy, seed))
+                .collect();
+
+            for (origin, window) in windows {
+                for y in 0..window.height {
+                    let start = (origin.1 + y) * self.width + origin.0;
+                    let row = &window.cells[y * window.width..(y + 1) * window.width];
+                    self.cells[start..start + window.width].copy_from_slice(row);
+                }
+                self.chunks.merge(&window.chunks, origin);
+            }
+        }
+    }
+
+    /// Simulates a single chunk on a copy of its surroundings.
+    /// Returns the position of the copy in the sandbox along with the updated copy.
+    #[cfg(feature = "parallel")]
+    fn update_window(&self, cx: usize, cy: usize, seed: u64) -> ((usize, usize), Sandbox) {
+        let (min, max) = self.chunks.bounds(cx, cy);
+        let origin = (min.0.saturating_sub(HALO), min.1.saturating_sub(HALO));
+        let end = (
+            (max.0 + HALO).min(self.width - 1),
+            (max.1 + HALO).min(self.height - 1),
+        );
+        let (width, height) = (end.0 - origin.0 + 1, end.1 - origin.1 + 1);
+
+        let mut cells = Vec::with_capacity(width * height);
+        for y in origin.1..=end.1 {
+            let start = y * self.width + origin.0;
+            cells.extend_from_slice(&self.cells[start..start + width]);
+        }
+
+        let mut chunks = Chunks::new(width, height);
+        chunks.advance();
+
+        let mut window = Sandbox {
+            cells,
+            width,
+            height,
+            gravity: self.gravity,
+            max_velocity: self.max_velocity,
+            ambient_temperature: self.ambient_temperature,
+            update_counter: self.update_counter,
+            ticks: self.ticks,
+            chunks,
+            rng: Rng::with_seed(seed),
+        };
+
+        for y in (min.1..=max.1).rev() {
+            let Some((min_x, max_x)) = self.chunks.row_span(cx, y) else {
+                continue;
+            };
+
+            let scan_right = window.rng.bool();
+            for j in 0..=(max_x - min_x) {
+                let x = if scan_right { min_x + j } else { max_x - j };
+                window.update_tracked((x - origin.0) as isize, (y - origin.1) as isize);
+            }
+        }
+
+        (origin, window)
+    }
+
+    /// Updates the cell and keeps its surroundings awake if anything is still going on there.
+    fn update_tracked(&mut self, x: isize, y: isize) {
+        let Some(before) = self.get(x, y) else {
+            return;
+        };
+
+        self.update_cell(x, y);
+
+        let Some(after) = self.get(x, y) else {
+            return;
+        };
+
+        let changed = before.get_type() != after.get_type()
+            || before.fuel != after.fuel
+            || (before.moisture - after.moisture).abs() > ACTIVITY_THRESHOLD
+            || (before.temperature - after.temperature).abs() > ACTIVITY_THRESHOLD
+            || (before.latent_heat - after.latent_heat).abs() > ACTIVITY_THRESHOLD;
+
+        if changed
+            || self.is_settling(x, y)
+            || self.can_move(x, y)
+            || self.has_pending_reaction(x, y)
+            || self.has_pending_moisture(x, y)
+            || self.is_compressed(x, y)
+        {
+            self.mark_active((x, y));
+        }
+    }
+
+    /// Whether the cell has latent heat stored or approaches an ambient value beyond one of
+    /// its transitions. Both can change it by less than the activity threshold per tick, but
+    /// add up over time. Any other drift towards the ambient values is caught up on waking.
+    fn is_settling(&self, x: isize, y: isize) -> bool {
+        let Some(cell) = self.get(x, y) else {
+            return false;
+        };
+
+        let crosses = |property: CellProperty, threshold: Threshold| {
+            let (value, ambient) = (cell.get_property(property), self.ambient_property(property));
+            cell.property_ambient_exchange(property) > 0.0
+                && match threshold {
+                    Threshold::Above(threshold) => value <= threshold && ambient > threshold,
+                    Threshold::Below(threshold) => value >= threshold && ambient < threshold,
+                }
+        };
+
+        let material = cell.material();
+        cell.latent_heat.abs() > ACTIVITY_THRESHOLD
+            || material
+                .transitions
+                .iter()
+                .any(|transition| crosses(CellProperty::Temperature, transition.threshold))
+            || material
+                .moisture_transitions
+                .iter()
+                .any(|transition| crosses(CellProperty::Moisture, transition.threshold))
+    }
+
+    /// Whether the cell isn't resting, settled cells keep cycling their velocity without moving.
+    fn can_move(&self, x: isize, y: isize) -> bool {
+        let Some(cell) = self.get(x, y) else {
+            return false;
+        };
+
+        let dy = if cell.is_buoyant() { -1 } else { 1 };
+        let falling = [(0, dy), (-1, dy), (1, dy)];
+        let spreading = [(-1, 0), (1, 0)];
+
+        match cell.movement() {
+            CellMovement::None => false,
+            CellMovement::Gas => true,
+            CellMovement::Powder if self.is_clinging(x, y) => false,
+            CellMovement::Powder if self.can_displace(cell, (x, y + dy)) => true,
+            CellMovement::Powder => {
+                falling[1..]
+                    .iter()
+                    .any(|&(dx, dy)| self.can_displace(cell, (x + dx, y + dy)))
+                    && self
+                        .get(x, y + dy)
+                        .is_some_and(|below| self.can_slide(cell, below))
+            }
+            CellMovement::Liquid => falling
+                .iter()
+                .chain(&spreading)
+                .any(|&(dx, dy)| self.can_displace(cell, (x + dx, y + dy))),
+        }
+    }
+
+    /// Whether a powder blocked below gathers enough sideways speed to slide off diagonally.
+    /// Every time it hits the cell below, part of its fall turns sideways and friction takes
+    /// some of that away again, which never adds up to a whole step on rough or wet ground.
+    fn can_slide(&self, cell: Cell, below: Cell) -> bool {
+        let fall = 1.0 + (self.gravity * cell.gravity_factor()).abs();
+        let transfer = fall * cell.slide_speed_factor() * below.slide_speed_factor();
+        let friction = below.slide_speed_factor() * (1.0 - cell.viscosity());
+
+        cell.vx.abs() >= 1.0
+            || cell.drift_impulse() > 0.0
+            || friction >= 1.0
+            || transfer * friction >= 1.0 - friction
+    }
+
+    /// Whether a reaction with any neighbour could still happen by chance.
+    fn has_pending_reaction(&self, x: isize, y: isize) -> bool {
+        let Some(cell) = self.get(x, y) else {
+            return false;
+        };
+
+        if !cell.material().reactive {
+            return false;
+        }
+
+        let reactions = MaterialRegistry::global().reactions();
+        NEIGHBOURS.iter().any(|&(dx, dy)| {
+            self.get(x + dx, y + dy).is_some_and(|other| {
+                reactions
+                    .get(cell.get_type(), other.get_type())
+                    .iter()
+                    .any(|reaction| reaction.is_possible(&cell, &other))
+            })
+        })
+    }
+
+    /// Whether the cell could still hand out moisture, spreading only picks a random neighbour
+    /// every tick and might miss the ones accepting it.
+    fn has_pending_moisture(&self, x: isize, y: isize) -> bool {
+        let property = CellProperty::Moisture;
+        let Some(cell) = self.get(x, y) else {
+            return false;
+        };
+
+        if cell.is_empty()
+            || cell.get_property(property) < cell.property_min_saturation(property)
+            || cell.property_diffuse_potential(property) <= 0.0
+        {
+            return false;
+        }
+
+        NEIGHBOURS.iter().any(|&(dx, dy)| {
+            self.get(x + dx, y + dy)
+                .is_some_and(|other| cell.spreads_to(&other, property))
+        })
+    }
+
+    /// Whether the cell is wet enough and has enough to hold on to, to stay where it is
+    /// instead of falling. It needs a resting cell below or diagonally below to lean on,
+    /// either itself or through a chain of other wet cells that are held in turn, so ledges
+    /// hang on while wet clumps still drop as a whole.
+    fn is_clinging(&self, x: isize, y: isize) -> bool {
+        const SIZE: usize = 2 * COHESION_REACH + 1;
+
+        if !self.can_cling(x, y) {
+            return false;
+        }
+
+        let reach = COHESION_REACH as isize;
+        let mut reached = [[false; SIZE]; SIZE];
+        reached[COHESION_REACH][COHESION_REACH] = true;
+        let mut frontier = vec![(x, y)];
+        while !frontier.is_empty() {
+            let mut next = Vec::new();
+            for (cx, cy) in frontier {
+                // Cells above can only be resting on the ones below them, which would
+                // make a clump hold on to itself.
+                if cy >= y
+                    && [(0, 1), (-1, 1), (1, 1)]
+                        .iter()
+                        .any(|&(dx, dy)| self.is_resting(cx + dx, cy + dy))
+                {
+                    return true;
+                }
+
+                for (dx, dy) in NEIGHBOURS {
+                    let (nx, ny) = (cx + dx, cy + dy);
+                    let (ox, oy) = (nx - x + reach, ny - y + reach);
+                    if !(0..SIZE as isize).contains(&ox) || !(0..SIZE as isize).contains(&oy) {
+                        continue;
+                    }
+                    let reached = &mut reached[oy as usize][ox as usize];
+                    if !*reached && self.can_cling(nx, ny) {
+                        *reached = true;
+                        next.push((nx, ny));
+                    }
+                }
+            }
+            frontier = next;
+        }
+        false
+    }
+
+    /// Whether the cell is wet, slow and surrounded enough to hold on to something.
+    fn can_cling(&self, x: isize, y: isize) -> bool {
+        let Some(cell) = self.get(x, y) else {
+            return false;
+        };
+
+        if !cell.is_cohesive() || cell.vy.abs() > COHESION_MAX_SPEED {
+            return false;
+        }
+
+        let holds = NEIGHBOURS
+            .iter()
+            .filter(|&&(dx, dy)| self.get(x + dx, y + dy).is_some_and(|n| !n.is_fluid()))
+            .count();
+        holds >= COHESION_NEIGHBOURS
+    }
+
+    /// Whether the cell is solid ground for others, either static or a powder that has
+    /// nowhere left to fall.
+    fn is_resting(&self, x: isize, y: isize) -> bool {
+        let Some(cell) = self.get(x, y) else {
+            return false;
+        };
+
+        if cell.is_fluid() {
+            return false;
+        }
+
+        matches!(cell.movement(), CellMovement::None)
+            || cell.vy.abs() <= COHESION_MAX_SPEED
+                && [(0, 1), (-1, 1), (1, 1)]
+                    .iter()
+                    .all(|&(dx, dy)| !self.can_displace(cell, (x + dx, y + dy)))
+    }
+
+    /// Whether enough cells rest on top of this one for it to compact.
+    fn is_compressed(&self, x: isize, y: isize) -> bool {
+        let Some(compaction) = self
+            .get(x, y)
+            .and_then(|cell| cell.material().compaction.as_ref())
+        else {
+            return false;
+        };
+
+        (1..=compaction.load as isize).all(|dy| {
+            self.get(x, y - dy)
+                .is_some_and(|above| !above.is_empty() && !above.is_static())
+        })
+    }
+
     pub fn draw(&self, frame: &mut [u8]) {
         for (cell, pixel) in self.cells.iter().zip(frame.chunks_exact_mut(4)) {
             pixel.copy_from_slice(&cell.color_rgba());
         }
     }
 
-    pub fn place(&mut self, x: isize, y: isize, cell: Cell) {
+    pub fn place(&mut self, x: isize, y: isize, mut cell: Cell) {
         if let Some(index) = self.coords_to_index(x, y) {
+            cell.simulated_at = self.ticks;
             self.cells[index] = cell;
+            self.mark_active((x, y));
+        }
+    }
+
+    /// Adds `amount` degrees to the cell at the given position, negative values cool it down.
+    pub fn heat(&mut self, x: isize, y: isize, amount: f32) {
+        if let Some(cell) = self.get_mut(x, y) {
+            cell.temperature += amount;
+            self.mark_active((x, y));
+        }
+    }
+
+    fn ambient_property(&self, property: CellProperty) -> f32 {
+        match property {
+            CellProperty::Moisture => 0.0,
+            CellProperty::Temperature => self.ambient_temperature,
         }
     }
 }
@@ -101,25 +577,303 @@ impl Sandbox {
             return;
         };
 
-        if cell.is_empty() || cell.last_updated == self.update_counter {
+        if cell.last_updated == self.update_counter {
+            return;
+        }
+
+        self.catch_up_ambient(x, y);
+        self.update_property(x, y, CellProperty::Temperature);
+        if cell.is_empty() {
             return;
         }
 
+        self.update_phase(x, y);
+        self.update_compaction(x, y);
+        self.update_reactions(x, y);
+        self.update_combustion(x, y);
+        self.update_scorching(x, y);
+
         self.update_property(x, y, CellProperty::Moisture);
+        self.update_evaporation(x, y);
+        self.update_saturation(x, y);
         self.update_movement(x, y);
     }
 
+    fn update_combustion(&mut self, x: isize, y: isize) {
+        let Some(cell) = self.get(x, y) else { return };
+        if !cell.is_burning() {
+            return;
+        }
+
+        let Some(combustion) = &cell.material().combustion else {
+            return;
+        };
+
+        let (dx, dy) = NEIGHBOURS[self.rng.usize(..NEIGHBOURS.len())];
+        if self.get(x + dx, y + dy).is_some_and(|n| n.is_empty()) {
+            let emission = if self.rng.f32() < combustion.flame_chance {
+                Some(Cell::fire())
+            } else if self.rng.f32() < combustion.smoke_chance {
+                Some(Cell::smoke())
+            } else {
+                None
+            };
+
+            if let Some(mut emission) = emission {
+                emission.last_updated = self.update_counter;
+                self.place(x + dx, y + dy, emission);
+            }
+        }
+
+        let update_counter = self.update_counter;
+        let Some(cell) = self.get_mut(x, y) else {
+            return;
+        };
+
+        cell.fuel -= 1.0;
+        if cell.temperature < combustion.burn_temperature {
+            cell.temperature =
+                (cell.temperature + combustion.heat).min(combustion.burn_temperature);
+        }
+
+        if cell.fuel <= 0.0 {
+            *cell = cell.convert(combustion.burns_into, update_counter);
+        }
+    }
+
+    /// Burning and scorching hot cells boil off touching moisture sources, cooling down in
+    /// the process, and set flammable neighbours alight.
+    fn update_scorching(&mut self, x: isize, y: isize) {
+        let Some(cell) = self.get(x, y) else { return };
+        if !cell.is_scorching() {
+            return;
+        }
+
+        for (dx, dy) in NEIGHBOURS {
+            let Some(neighbour) = self.get(x + dx, y + dy) else {
+                continue;
+            };
+
+            if neighbour.is_pure_source(CellProperty::Moisture) {
+                self.boil_off(x + dx, y + dy, QUENCH_MOISTURE);
+                self.heat(x, y, -QUENCH_COOLING);
+            } else {
+                self.try_ignite(x + dx, y + dy);
+            }
+        }
+    }
+
+    fn try_ignite(&mut self, x: isize, y: isize) {
+        let Some(index) = self.coords_to_index(x, y) else {
+            return;
+        };
+        let cell = &mut self.cells[index];
+
+        if !cell.is_flammable() || cell.is_burning() {
+            return;
+        }
+
+        let Some(combustion) = &cell.material().combustion else {
+            return;
+        };
+
+        // Start with one tick worth of heat so conduction can't put the flame out right away.
+        if self.rng.f32() < combustion.flammability {
+            cell.temperature = cell
+                .temperature
+                .max(combustion.ignition_temperature + combustion.heat);
+        }
+    }
+
+    /// Removes moisture from a cell by boiling it away.
+    /// Moisture sources that run dry turn into their vapor instead of just vanishing.
+    fn boil_off(&mut self, x: isize, y: isize, amount: f32) {
+        let update_counter = self.update_counter;
+        let Some(cell) = self.get_mut(x, y) else {
+            return;
+        };
+
+        cell.moisture = (cell.moisture - amount).max(0.0);
+        if cell.is_pure_source(CellProperty::Moisture) && cell.moisture <= DEPLETION_THRESHOLD {
+            let vapor = cell.material().vapor.unwrap_or(CellType::EMPTY);
+            let mut vapor = cell.convert(vapor, update_counter);
+            // Boiled off vapor is at least as hot as it starts out on its own.
+            vapor.temperature = vapor
+                .temperature
+                .max(vapor.get_type().inherent_temperature());
+            *cell = vapor;
+        }
+    }
+
+    /// Hot cells lose the moisture they hold, cooling down in the process.
+    fn update_evaporation(&mut self, x: isize, y: isize) {
+        let Some(cell) = self.get_mut(x, y) else {
+            return;
+        };
+
+        if cell.temperature <= BOILING_POINT
+            || cell.moisture <= 0.0
+            || cell.is_pure_source(CellProperty::Moisture)
+        {
+            return;
+        }
+
+        let amount = cell.moisture.min(EVAPORATION_RATE);
+        cell.moisture -= amount;
+        cell.temperature -= amount * EVAPORATION_COOLING;
+    }
+
+    /// Cells that got too wet or dried out turn into another material.
+    fn update_saturation(&mut self, x: isize, y: isize) {
+        let Some(cell) = self.get(x, y) else { return };
+
+        let transitions = &cell.material().moisture_transitions;
+        let Some(transition) = transitions
+            .iter()
+            .find(|transition| match transition.threshold {
+                Threshold::Above(threshold) => cell.moisture > threshold,
+                Threshold::Below(threshold) => cell.moisture < threshold,
+            })
+        else {
+            return;
+        };
+
+        self.place(x, y, cell.convert(transition.into, self.update_counter));
+    }
+
+    fn update_phase(&mut self, x: isize, y: isize) {
+        let update_counter = self.update_counter;
+        let Some(cell) = self.get_mut(x, y) else {
+            return;
+        };
+
+        for transition in cell.get_type().transitions() {
+            // Positive latent heat belongs to transitions above a threshold, negative to those below.
+            let (threshold, beyond, sign) = match transition.threshold {
+                Threshold::Above(threshold) => (threshold, cell.temperature - threshold, 1.0),
+                Threshold::Below(threshold) => (threshold, threshold - cell.temperature, -1.0),
+            };
+
+            if beyond > 0.0 {
+                cell.latent_heat += beyond * sign;
+                cell.temperature = threshold;
+
+                if cell.latent_heat * sign >= transition.latent_heat {
+                    *cell = cell.convert(transition.into, update_counter);
+                }
+                return;
+            }
+
+            // Release heat absorbed by an interrupted transition before anything else happens.
+            let stored = cell.latent_heat * sign;
+            if stored > 0.0 {
+                let released = stored.min(-beyond);
+                cell.latent_heat -= released * sign;
+                cell.temperature += released * sign;
+            }
+        }
+    }
+
+    fn update_compaction(&mut self, x: isize, y: isize) {
+        let Some(cell) = self.get(x, y) else { return };
+        let Some(compaction) = &cell.material().compaction else {
+            return;
+        };
+
+        if self.rng.f32() >= compaction.chance || !self.is_compressed(x, y) {
+            return;
+        }
+
+        self.place(x, y, cell.convert(compaction.into, self.update_counter));
+    }
+
+    fn update_reactions(&mut self, x: isize, y: isize) {
+        let Some(cell) = self.get(x, y) else { return };
+        if !cell.material().reactive {
+            return;
+        }
+
+        let (dx, dy) = NEIGHBOURS[self.rng.usize(..NEIGHBOURS.len())];
+        let Some(other) = self.get(x + dx, y + dy) else {
+            return;
+        };
+
+        let reactions = MaterialRegistry::global().reactions();
+        let Some(reaction) = reactions
+            .get(cell.get_type(), other.get_type())
+            .iter()
+            .find(|reaction| reaction.is_possible(&cell, &other))
+        else {
+            return;
+        };
+
+        if self.rng.f32() >= reaction.probability {
+            return;
+        }
+
+        let first = reaction.product(0, cell, self.update_counter);
+        let second = reaction.product(1, other, self.update_counter);
+        self.place(x, y, first);
+        self.place(x + dx, y + dy, second);
+    }
+
     fn update_movement(&mut self, x: isize, y: isize) {
         let Some(cell) = self.get(x, y) else { return };
 
         match cell.movement() {
             CellMovement::None => {}
-            CellMovement::Powder => self.move_with_velocity(x, y),
+            CellMovement::Powder if self.is_clinging(x, y) => {}
+            CellMovement::Powder => self.move_powder(x, y),
             CellMovement::Liquid => self.move_with_velocity(x, y),
-            CellMovement::Gas => {}
+            CellMovement::Gas => self.move_gas(x, y),
         }
     }
 
+    fn move_powder(&mut self, x: isize, y: isize) {
+        let Some(cell) = self.get(x, y) else {
+            return;
+        };
+
+        // Light powders like snow drift around until they come to rest.
+        if cell.drag() > 0.0 || cell.drift_impulse() > 0.0 {
+            let drift = if self.can_move(x, y) {
+                cell.drift_impulse() * (self.rng.f32() * 2.0 - 1.0)
+            } else {
+                0.0
+            };
+
+            if let Some(cell) = self.get_mut(x, y) {
+                let drag = 1.0 - cell.drag();
+                cell.vx = cell.vx * drag + drift;
+                cell.vy *= drag;
+            }
+        }
+
+        self.move_with_velocity(x, y);
+    }
+
+    fn move_gas(&mut self, x: isize, y: isize) {
+        let Some(cell) = self.get(x, y) else {
+            return;
+        };
+
+        if self.rng.f32() < cell.dissipation_chance() {
+            self.place(x, y, Cell::default());
+            return;
+        }
+
+        let Some(index) = self.coords_to_index(x, y) else {
+            return;
+        };
+        let cell = &mut self.cells[index];
+
+        let drag = 1.0 - cell.drag();
+        cell.vx = cell.vx * drag + cell.drift_impulse() * (self.rng.f32() * 2.0 - 1.0);
+        cell.vy *= drag;
+
+        self.move_with_velocity(x, y);
+    }
+
     fn move_with_velocity(&mut self, x: isize, y: isize) {
         let Some(mut cell) = self.get(x, y) else {
             return;
@@ -133,7 +887,7 @@ impl Sandbox {
         let vy_dir = cell.vy.signum() as isize;
         for _ in 0..cell.vy.abs().floor() as usize {
             let next = (current.0, current.1 + vy_dir);
-            if self.can_displace(cell, next) {
+            if self.try_displace(cell, next) {
                 self.swap_cells(current, next);
                 current = next;
             } else {
@@ -144,10 +898,19 @@ impl Sandbox {
             }
         }
 
+        // Viscous cells only flow now and then and lose most of their sideways momentum
+        // afterwards, so they pile up instead of levelling out right away.
+        let spreads = cell.viscosity() <= 0.0 || self.rng.f32() >= cell.viscosity();
+
         let vx_dir = cell.vx.signum() as isize;
-        for _ in 0..cell.vx.abs().floor() as usize {
+        let vx_steps = if spreads {
+            cell.vx.abs().floor() as usize
+        } else {
+            0
+        };
+        for _ in 0..vx_steps {
             let next = (current.0 + vx_dir, current.1);
-            if self.can_displace(cell, next) {
+            if self.try_displace(cell, next) {
                 self.swap_cells(current, next);
                 current = next;
             } else {
@@ -157,11 +920,12 @@ impl Sandbox {
             }
         }
 
+        let surface_dir = if cell.is_buoyant() { -1 } else { 1 };
         let surface_friction = self
-            .get(current.0, current.1 + 1)
+            .get(current.0, current.1 + surface_dir)
             .map(|below| below.slide_speed_factor())
             .unwrap_or(0.5);
-        cell.vx *= surface_friction;
+        cell.vx *= surface_friction * (1.0 - cell.viscosity());
 
         if let Some(c) = self.get_mut(current.0, current.1) {
             c.vx = cell.vx;
@@ -169,7 +933,7 @@ impl Sandbox {
         }
     }
 
-    fn push_slide_down(&self, cell: &mut Cell, pos: (isize, isize), vy_dir: isize) {
+    fn push_slide_down(&mut self, cell: &mut Cell, pos: (isize, isize), vy_dir: isize) {
         let Some(blocker) = self.get(pos.0, pos.1 + vy_dir) else {
             return;
         };
@@ -190,15 +954,22 @@ impl Sandbox {
     }
 
     fn push_blocker_vertical(&mut self, _from: (isize, isize), to: (isize, isize), impulse: f32) {
-        let Some(blocker) = self.get_mut(to.0, to.1) else {
+        let Some(index) = self.coords_to_index(to.0, to.1) else {
             return;
         };
-        if blocker.is_empty() {
+        let blocker = &mut self.cells[index];
+        if blocker.is_empty() || blocker.is_static() {
+            return;
+        }
+
+        // Only push along the direction the blocker would fall by itself,
+        // so rising gas can't lift sand and falling sand can't sink gas.
+        if blocker.is_buoyant() != (impulse < 0.0) {
             return;
         }
 
         blocker.vy += impulse;
-        blocker.vx += impulse * 0.2 * if fastrand::bool() { 1.0 } else { -1.0 };
+        blocker.vx += impulse * 0.2 * if self.rng.bool() { 1.0 } else { -1.0 };
     }
 
     fn push_blocker_horizontal(&mut self, to: (isize, isize), impulse: f32) {
@@ -206,19 +977,19 @@ impl Sandbox {
             return;
         };
 
-        if blocker.is_empty() {
+        if blocker.is_empty() || blocker.is_static() {
             return;
         }
 
         blocker.vx += impulse;
     }
 
-    fn find_open_direction(&self, cell: Cell, pos: (isize, isize), dy: isize) -> Option<isize> {
+    fn find_open_direction(&mut self, cell: Cell, pos: (isize, isize), dy: isize) -> Option<isize> {
         let left = self.can_displace(cell, (pos.0 - 1, pos.1 + dy));
         let right = self.can_displace(cell, (pos.0 + 1, pos.1 + dy));
 
         match (left, right) {
-            (true, true) => Some(if fastrand::bool() { 1 } else { -1 }),
+            (true, true) => Some(if self.rng.bool() { 1 } else { -1 }),
             (true, false) => Some(-1),
             (false, true) => Some(1),
             (false, false) => None,
@@ -226,28 +997,115 @@ impl Sandbox {
     }
 
     fn update_property(&mut self, x: isize, y: isize, property: CellProperty) {
+        self.exchange_ambient(x, y, property);
+
         let Some(source) = self.get(x, y) else { return };
-        if source.is_empty() {
+        if source.get_property(property) < source.property_min_saturation(property) {
             return;
         }
 
-        if source.get_property(property) < source.property_min_saturation(property) {
+        let mut candidates = NEIGHBOURS.map(|(dx, dy)| (x + dx, y + dy));
+
+        match property {
+            CellProperty::Moisture => {
+                if source.is_empty() {
+                    return;
+                }
+
+                self.rng.shuffle(&mut candidates);
+                self.try_spread_property(x, y, &candidates, property);
+            }
+            CellProperty::Temperature => self.conduct_property(x, y, &candidates, property),
+        }
+    }
+
+    /// Applies the exchange with the surroundings the cell missed while it was asleep.
+    fn catch_up_ambient(&mut self, x: isize, y: isize) {
+        let ticks = self.ticks;
+        let ambient = [CellProperty::Moisture, CellProperty::Temperature]
+            .map(|property| (property, self.ambient_property(property)));
+        let Some(cell) = self.get_mut(x, y) else {
+            return;
+        };
+
+        let skipped = ticks.wrapping_sub(cell.simulated_at).saturating_sub(1);
+        cell.simulated_at = ticks;
+        if skipped == 0 {
+            return;
+        }
+
+        for (property, ambient) in ambient {
+            let exchange = cell.property_ambient_exchange(property);
+            if exchange <= 0.0 {
+                continue;
+            }
+
+            let remaining = (1.0 - exchange).powf(skipped as f32);
+            let value = cell.get_property(property);
+            cell.set_property(property, ambient + (value - ambient) * remaining);
+        }
+    }
+
+    fn exchange_ambient(&mut self, x: isize, y: isize, property: CellProperty) {
+        let ambient = self.ambient_property(property);
+        let Some(cell) = self.get_mut(x, y) else {
+            return;
+        };
+
+        let exchange = cell.property_ambient_exchange(property);
+        if exchange <= 0.0 {
             return;
         }
 
-        let mut candidates = [
-            (x, y + 1),
-            (x - 1, y + 1),
-            (x + 1, y + 1),
-            (x - 1, y),
-            (x + 1, y),
-            (x, y - 1),
-            (x - 1, y - 1),
-            (x + 1, y - 1),
-        ];
+        let value = cell.get_property(property);
+        cell.set_property(property, value + (ambient - value) * exchange);
+    }
+
+    /// Moves the property from the source to every neighbour holding less of it,
+    /// proportional to the difference, without ever overshooting the shared equilibrium.
+    fn conduct_property(
+        &mut self,
+        x: isize,
+        y: isize,
+        candidates: &[(isize, isize)],
+        property: CellProperty,
+    ) {
+        for &(tx, ty) in candidates {
+            let (Some(source), Some(target)) = (self.get(x, y), self.get(tx, ty)) else {
+                continue;
+            };
+
+            let difference = source.get_property(property) - target.get_property(property);
+            if difference <= 0.01 {
+                continue;
+            }
 
-        fastrand::shuffle(&mut candidates);
-        self.try_spread_property(x, y, &candidates, property);
+            let source_capacity = source.property_capacity(property);
+            let target_capacity = target.property_capacity(property);
+            if source_capacity <= 0.0 || target_capacity <= 0.0 {
+                continue;
+            }
+
+            let rate = source
+                .property_diffusion_rate(property)
+                .min(target.property_accept_rate(property));
+            let equilibrium = difference / (1.0 / source_capacity + 1.0 / target_capacity);
+            let transfer = equilibrium * rate;
+
+            if let Some(target) = self.get_mut(tx, ty) {
+                target.set_property(
+                    property,
+                    target.get_property(property) + transfer / target_capacity,
+                );
+            }
+
+            if let Some(source) = self.get_mut(x, y) {
+                source.set_property(
+                    property,
+                    source.get_property(property) - transfer / source_capacity,
+                );
+            }
+        }
     }
 
     fn try_spread_property(
@@ -261,17 +1119,9 @@ impl Sandbox {
             return false;
         };
 
-        let source_value = source.get_property(property);
-        let source_is_pure = source.is_pure_source(property);
-
         let Some(&(tx, ty)) = candidates.iter().find(|&&(tx, ty)| {
             self.get(tx, ty)
-                .map(|t| {
-                    !t.is_empty() && source_is_pure
-                        || (t.get_property(property) < source_value
-                            && t.property_accept_potential(property) > 0.0)
-                })
-                .unwrap_or(false)
+                .is_some_and(|target| source.spreads_to(&target, property))
         }) else {
             return false;
         };
@@ -300,8 +1150,292 @@ impl Sandbox {
     fn check_depletion(&mut self, x: isize, y: isize, property: CellProperty) {
         let Some(cell) = self.get(x, y) else { return };
 
-        if cell.is_pure_source(property) && cell.get_property(property) <= 0.05 {
+        if cell.is_pure_source(property) && cell.get_property(property) <= DEPLETION_THRESHOLD {
             self.place(x, y, Cell::default());
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(sandbox: &Sandbox, type_: CellType) -> usize {
+        sandbox
+            .cells
+            .iter()
+            .filter(|cell| cell.get_type() == type_)
+            .count()
+    }
+
+    fn run(sandbox: &mut Sandbox, ticks: usize) {
+        for _ in 0..ticks {
+            sandbox.update();
+        }
+    }
+
+    /// Melting absorbs heat slower than the activity threshold per tick, the block
+    /// mustn't fall asleep halfway through.
+    #[test]
+    fn ice_melts_at_room_temperature() {
+        let mut sandbox = Sandbox::with_seed(64, 32, 1);
+        for y in 10..20 {
+            for x in 4..14 {
+                sandbox.place(x, y, Cell::ice());
+            }
+        }
+
+        run(&mut sandbox, 3_500);
+
+        assert_eq!(count(&sandbox, CellType::ICE), 0);
+        assert!(count(&sandbox, CellType::WATER) > 0);
+    }
+
+    /// Drying and cooling towards the surroundings doesn't keep cells awake,
+    /// it is caught up on once something wakes them.
+    #[test]
+    fn settled_cells_fall_asleep_and_catch_up() {
+        let mut sandbox = Sandbox::with_seed(64, 32, 1);
+        for y in 16..32 {
+            for x in 0..32 {
+                let mut sand = Cell::sand();
+                sand.moisture = 1.0;
+                sandbox.place(x, y, sand);
+            }
+        }
+        for y in 8..32 {
+            for x in 36..64 {
+                let mut cell = if y == 8 || y == 31 || x == 36 || x == 63 {
+                    Cell::new(CellType::WALL)
+                } else {
+                    Cell::new(CellType::STONE)
+                };
+                cell.temperature = 300.0;
+                sandbox.place(x, y, cell);
+            }
+        }
+
+        run(&mut sandbox, 300);
+        for y in 0..32 {
+            for x in 0..64 {
+                assert!(!sandbox.is_awake(x, y), "{x} {y} is awake");
+            }
+        }
+
+        let (before_sand, before_stone) =
+            (sandbox.get(10, 20).unwrap(), sandbox.get(50, 20).unwrap());
+        run(&mut sandbox, 1000);
+        assert_eq!(
+            sandbox.get(50, 20).unwrap().temperature,
+            before_stone.temperature
+        );
+
+        sandbox.set_ambient_temperature(AMBIENT_TEMPERATURE);
+        run(&mut sandbox, 1);
+        let ticks = sandbox.ticks;
+        let decay = |cell: Cell, property: CellProperty, ambient: f32| {
+            let elapsed = ticks - cell.simulated_at;
+            let remaining = (1.0 - cell.property_ambient_exchange(property)).powi(elapsed as i32);
+            ambient + (cell.get_property(property) - ambient) * remaining
+        };
+        let (sand, stone) = (sandbox.get(10, 20).unwrap(), sandbox.get(50, 20).unwrap());
+        let expected = decay(before_sand, CellProperty::Moisture, 0.0);
+        assert!(
+            (sand.moisture - expected).abs() < 0.01,
+            "{} {expected}",
+            sand.moisture
+        );
+        let expected = decay(before_stone, CellProperty::Temperature, AMBIENT_TEMPERATURE);
+        assert!(
+            (stone.temperature - expected).abs() < 0.5,
+            "{} {expected}",
+            stone.temperature
+        );
+    }
+
+    /// Both updates use the random numbers differently, so only the overall outcome can match.
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_update_matches_serial_one() {
+        let layered = |parallel: bool| {
+            let mut sandbox = Sandbox::with_seed(96, 64, 1);
+            for y in 20..44 {
+                for x in 8..88 {
+                    let type_ = if (x + y) % 2 == 0 {
+                        CellType::OIL
+                    } else {
+                        CellType::WATER
+                    };
+                    sandbox.place(x, y, Cell::new(type_));
+                }
+            }
+
+            for _ in 0..250 {
+                if parallel {
+                    sandbox.update();
+                } else {
+                    sandbox.update_counter = sandbox.update_counter.wrapping_add(1);
+                    sandbox.ticks = sandbox.ticks.wrapping_add(1);
+                    sandbox.chunks.advance();
+                    sandbox.update_rows();
+                }
+            }
+
+            let height = |type_: CellType| {
+                let ys: Vec<_> = (0..sandbox.height)
+                    .flat_map(|y| (0..sandbox.width).map(move |x| (x, y)))
+                    .filter(|&(x, y)| sandbox.cells[y * sandbox.width + x].get_type() == type_)
+                    .map(|(_, y)| y as f32)
+                    .collect();
+                (ys.len(), ys.iter().sum::<f32>() / ys.len() as f32)
+            };
+            (height(CellType::OIL), height(CellType::WATER))
+        };
+
+        let (serial_oil, serial_water) = layered(false);
+        let (parallel_oil, parallel_water) = layered(true);
+
+        assert_eq!(serial_oil.0, parallel_oil.0);
+        assert_eq!(serial_water.0, parallel_water.0);
+        assert!(serial_oil.1 + 4.0 < serial_water.1, "oil floats on water");
+        assert!(
+            parallel_oil.1 + 4.0 < parallel_water.1,
+            "oil floats on water"
+        );
+        assert!((serial_oil.1 - parallel_oil.1).abs() < 1.0);
+        assert!((serial_water.1 - parallel_water.1).abs() < 1.0);
+    }
+
+    /// Freshly boiled steam has to get well away from the water before it rains back down.
+    #[test]
+    fn steam_rises_before_condensing() {
+        const START: isize = 120;
+        let mut sandbox = Sandbox::with_seed(32, 128, 1);
+        for x in 6..26 {
+            let mut steam = Cell::steam();
+            steam.temperature = BOILING_POINT;
+            sandbox.place(x, START, steam);
+        }
+
+        for _ in 0..500 {
+            sandbox.update();
+            let lowest_water = (0..sandbox.height as isize).rev().find(|&y| {
+                (0..sandbox.width as isize)
+                    .any(|x| sandbox.get(x, y).unwrap().get_type() == CellType::WATER)
+            });
+
+            if let Some(y) = lowest_water {
+                assert!(
+                    START - y >= 40,
+                    "steam condensed after rising {}",
+                    START - y
+                );
+                return;
+            }
+        }
+        panic!("steam never condensed");
+    }
+
+    #[test]
+    fn snow_melts_at_room_temperature() {
+        let mut sandbox = Sandbox::with_seed(64, 32, 1);
+        for y in 22..32 {
+            for x in 40..50 {
+                sandbox.place(x, y, Cell::snow());
+            }
+        }
+
+        run(&mut sandbox, 3_500);
+
+        assert_eq!(count(&sandbox, CellType::SNOW), 0);
+        assert_eq!(count(&sandbox, CellType::ICE), 0);
+    }
+
+    fn wet_sand() -> Cell {
+        let mut cell = Cell::new(CellType::SAND);
+        cell.moisture = 1.4;
+        cell
+    }
+
+    #[test]
+    fn wet_sand_only_clings_with_ground_below() {
+        let mut sandbox = Sandbox::with_seed(64, 64, 1);
+        for y in 10..20 {
+            for x in 10..20 {
+                sandbox.place(x, y, wet_sand());
+            }
+        }
+        for y in 40..64 {
+            for x in 40..44 {
+                sandbox.place(x, y, wet_sand());
+            }
+        }
+
+        run(&mut sandbox, 200);
+
+        let sand_in = |xs: std::ops::Range<isize>, ys: std::ops::Range<isize>| {
+            ys.flat_map(|y| xs.clone().map(move |x| (x, y)))
+                .filter(|&(x, y)| sandbox.get(x, y).unwrap().get_type() == CellType::SAND)
+                .count()
+        };
+        assert_eq!(sand_in(0..32, 0..40), 0, "floating clump fell down");
+        assert_eq!(sand_in(40..44, 40..64), 96, "column kept standing");
+    }
+
+    #[test]
+    fn wet_sand_ledges_hang_on_to_their_column() {
+        let mut sandbox = Sandbox::with_seed(64, 64, 1);
+        for y in 24..64 {
+            for x in 20..26 {
+                sandbox.place(x, y, wet_sand());
+            }
+        }
+        for y in 26..30 {
+            for x in 26..34 {
+                sandbox.place(x, y, wet_sand());
+            }
+        }
+
+        run(&mut sandbox, 300);
+
+        for y in 26..30 {
+            for x in 26..34 {
+                assert_eq!(sandbox.get(x, y).unwrap().get_type(), CellType::SAND);
+            }
+        }
+        assert_eq!(count(&sandbox, CellType::SAND), 6 * 40 + 8 * 4);
+    }
+
+    #[test]
+    fn lava_scorches_water_but_hot_metal_does_not() {
+        let mut lava = Sandbox::with_seed(32, 32, 1);
+        let mut metal = Sandbox::with_seed(32, 32, 1);
+        for x in 0..32 {
+            lava.place(x, 31, Cell::new(CellType::WATER));
+            lava.place(x, 30, Cell::new(CellType::WATER));
+
+            let mut hot_metal = Cell::new(CellType::METAL);
+            hot_metal.temperature = 1500.0;
+            metal.place(x, 31, hot_metal);
+            metal.place(x, 30, Cell::new(CellType::WATER));
+        }
+        for x in 12..20 {
+            lava.place(x, 20, Cell::new(CellType::LAVA));
+        }
+
+        run(&mut lava, 100);
+        run(&mut metal, 100);
+
+        assert_eq!(count(&lava, CellType::LAVA), 0);
+        assert!(count(&lava, CellType::STONE) > 0);
+        assert_eq!(count(&metal, CellType::WATER), 32, "no water boiled off");
+        assert!(
+            metal
+                .cells
+                .iter()
+                .filter(|cell| cell.get_type() == CellType::WATER)
+                .all(|cell| cell.moisture >= Cell::new(CellType::WATER).moisture),
+            "no water boiled off"
+        );
+    }
+}
@@ -0,0 +1,170 @@
+use super::Sandbox;
+use crate::cell::Cell;
+use crate::chunk::CHUNK_SIZE;
+
+/// Colour of everything outside of the sandbox when drawing a region.
+pub const OUTSIDE_COLOR: [u8; 4] = [26, 26, 26, 255];
+/// Temperatures at both ends of the temperature heatmap.
+const COLDEST: f32 = -50.0;
+const HOTTEST: f32 = 1000.0;
+/// Stops of the heatmap gradient, from low to high values.
+const HEATMAP: [[u8; 3]; 5] = [
+    [0, 0, 0],
+    [40, 40, 200],
+    [0, 200, 200],
+    [240, 220, 0],
+    [255, 255, 255],
+];
+
+/// What the colour of a drawn cell shows.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RenderMode {
+    /// The material colour, the way the sandbox is meant to look.
+    #[default]
+    Material,
+    /// Heatmap of the moisture held by every cell.
+    Moisture,
+    Temperature,
+    /// Movement direction as hue and speed as brightness.
+    Velocity,
+    /// Heatmap of the material density.
+    Density,
+    /// Highlights the cells changed by the last update.
+    Updated,
+    /// Dims sleeping cells and outlines the chunks.
+    Sleep,
+}
+
+impl RenderMode {
+    pub const ALL: [(Self, &'static str); 7] = [
+        (Self::Material, "Material"),
+        (Self::Moisture, "Moisture"),
+        (Self::Temperature, "Temperature"),
+        (Self::Velocity, "Velocity"),
+        (Self::Density, "Density"),
+        (Self::Updated, "Updated"),
+        (Self::Sleep, "Sleep"),
+    ];
+}
+
+impl Sandbox {
+    /// Draws the part of the sandbox visible in a frame `frame_width` pixels wide,
+    /// with `origin` being the cell position at its top left corner and every cell covering
+    /// `zoom` pixels in both directions. Pixels outside the sandbox get the [`OUTSIDE_COLOR`].
+    pub fn draw_region(
+        &self,
+        frame: &mut [u8],
+        frame_width: usize,
+        origin: (f32, f32),
+        zoom: f32,
+        mode: RenderMode,
+    ) {
+        let row_len = frame_width * 4;
+        let mut previous_row: Option<(usize, isize)> = None;
+
+        for py in 0..frame.len() / row_len.max(1) {
+            let y = (origin.1 + (py as f32 + 0.5) / zoom).floor() as isize;
+
+            // Zoomed in, neighbouring pixel rows show the same cells.
+            if let Some((previous, previous_y)) = previous_row
+                && previous_y == y
+            {
+                frame.copy_within(previous * row_len..(previous + 1) * row_len, py * row_len);
+                continue;
+            }
+            previous_row = Some((py, y));
+
+            let row = &mut frame[py * row_len..(py + 1) * row_len];
+            let mut previous_cell: Option<(isize, [u8; 4])> = None;
+            for (px, pixel) in row.chunks_exact_mut(4).enumerate() {
+                let x = (origin.0 + (px as f32 + 0.5) / zoom).floor() as isize;
+                let color = match previous_cell {
+                    Some((previous_x, color)) if previous_x == x => color,
+                    _ => self.color_at(x, y, mode),
+                };
+
+                pixel.copy_from_slice(&color);
+                previous_cell = Some((x, color));
+            }
+        }
+    }
+
+    /// The colour of the cell at the given position in the given render mode.
+    pub fn color_at(&self, x: isize, y: isize, mode: RenderMode) -> [u8; 4] {
+        let Some(cell) = self.get(x, y) else {
+            return OUTSIDE_COLOR;
+        };
+
+        match mode {
+            RenderMode::Material => cell.color_rgba(),
+            RenderMode::Moisture => heatmap(cell.moisture),
+            RenderMode::Temperature => heatmap((cell.temperature - COLDEST) / (HOTTEST - COLDEST)),
+            RenderMode::Velocity => self.velocity_color(&cell),
+            RenderMode::Density => heatmap((cell.density() as f32 / u8::MAX as f32).sqrt()),
+            RenderMode::Updated => {
+                if cell.last_updated == self.update_counter {
+                    [255, 60, 60, 255]
+                } else {
+                    dim(cell.color_rgba())
+                }
+            }
+            RenderMode::Sleep => {
+                let on_border = (x as usize).is_multiple_of(CHUNK_SIZE)
+                    || (y as usize).is_multiple_of(CHUNK_SIZE);
+                if on_border {
+                    [90, 90, 90, 255]
+                } else if self.is_awake(x, y) {
+                    let [r, g, b, a] = cell.color_rgba();
+                    [r / 2, g / 2 + 100, b / 2, a]
+                } else {
+                    dim(cell.color_rgba())
+                }
+            }
+        }
+    }
+
+    fn velocity_color(&self, cell: &Cell) -> [u8; 4] {
+        let speed = (cell.vx * cell.vx + cell.vy * cell.vy).sqrt();
+        if speed == 0.0 {
+            return [0, 0, 0, 255];
+        }
+
+        let hue = cell.vy.atan2(cell.vx).to_degrees().rem_euclid(360.0);
+        let brightness = (speed / self.max_velocity).clamp(0.0, 1.0).sqrt();
+        let [r, g, b] = hue_to_rgb(hue).map(|channel| (channel * brightness * 255.0) as u8);
+        [r, g, b, 255]
+    }
+}
+
+/// Colour of `value` between 0 and 1 on the heatmap gradient, values outside are clamped.
+fn heatmap(value: f32) -> [u8; 4] {
+    let scaled = value.clamp(0.0, 1.0) * (HEATMAP.len() - 1) as f32;
+    let index = (scaled as usize).min(HEATMAP.len() - 2);
+    let t = scaled - index as f32;
+
+    let [r, g, b] = std::array::from_fn(|channel| {
+        let (from, to) = (
+            HEATMAP[index][channel] as f32,
+            HEATMAP[index + 1][channel] as f32,
+        );
+        (from + (to - from) * t) as u8
+    });
+    [r, g, b, 255]
+}
+
+/// Fully saturated colour of the given hue in degrees.
+fn hue_to_rgb(hue: f32) -> [f32; 3] {
+    let x = 1.0 - ((hue / 60.0) % 2.0 - 1.0).abs();
+    match hue as u32 / 60 {
+        0 => [1.0, x, 0.0],
+        1 => [x, 1.0, 0.0],
+        2 => [0.0, 1.0, x],
+        3 => [0.0, x, 1.0],
+        4 => [x, 0.0, 1.0],
+        _ => [1.0, 0.0, x],
+    }
+}
+
+fn dim([r, g, b, a]: [u8; 4]) -> [u8; 4] {
+    [r / 3, g / 3, b / 3, a]
+}
@@ -0,0 +1,425 @@
+use super::Sandbox;
+use crate::cell::Cell;
+use crate::chunk::{Chunks, HALO};
+use crate::material::MaterialRegistry;
+use fastrand::Rng;
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+
+const MAGIC: [u8; 4] = *b"LSND";
+/// Bumped whenever the layout changes in a way older versions can't read.
+/// New cell fields don't need a new version, see [`CELL_FIELDS`].
+pub const FORMAT_VERSION: u16 = 2;
+/// Largest width or height a save may have, far beyond anything that could be simulated anyway.
+const MAX_DIMENSION: usize = 1 << 16;
+
+/// Per-cell fields with their size in bytes, in the order they are written.
+/// Every field is stored for all cells at once, which compresses a lot better.
+/// Loading skips fields it doesn't know and keeps the defaults of [`Cell::new`] for missing ones,
+/// so fields can be added without breaking older saves. The type always has to come first.
+const CELL_FIELDS: [(&str, u8); 9] = [
+    ("type", 2),
+    ("vx", 4),
+    ("vy", 4),
+    ("moisture", 4),
+    ("temperature", 4),
+    ("latent_heat", 4),
+    ("fuel", 4),
+    ("last_updated", 1),
+    ("simulated_at", 4),
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum SaveError {
+    #[error("failed to access save file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("not a sandbox save file")]
+    InvalidMagic,
+    #[error("save file version {0} is not supported, at most version {FORMAT_VERSION} is")]
+    UnsupportedVersion(u16),
+    #[error("save file references unknown material '{0}'")]
+    UnknownMaterial(String),
+    #[error("cell field '{name}' has {size} bytes, expected {expected}")]
+    InvalidFieldSize {
+        name: String,
+        size: u8,
+        expected: u8,
+    },
+    #[error("save file is corrupt: {0}")]
+    Corrupt(&'static str),
+}
+
+impl Sandbox {
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SaveError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.save_to(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SaveError> {
+        Self::load_from(BufReader::new(File::open(path)?))
+    }
+
+    /// Writes the whole simulation state, materials are stored by name so saves
+    /// stay valid when the registry changes.
+    pub fn save_to(&self, mut writer: impl Write) -> Result<(), SaveError> {
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+
+        let mut out = ZlibEncoder::new(writer, Compression::default());
+        out.write_all(&(self.width as u32).to_le_bytes())?;
+        out.write_all(&(self.height as u32).to_le_bytes())?;
+        out.write_all(&self.gravity.to_le_bytes())?;
+        out.write_all(&self.max_velocity.to_le_bytes())?;
+        out.write_all(&self.ambient_temperature.to_le_bytes())?;
+        out.write_all(&[self.update_counter])?;
+        out.write_all(&self.ticks.to_le_bytes())?;
+        out.write_all(&self.rng.get_seed().to_le_bytes())?;
+
+        let registry = MaterialRegistry::global();
+        out.write_all(&(registry.len() as u16).to_le_bytes())?;
+        for (_, material) in registry.iter() {
+            write_string(&mut out, &material.name)?;
+        }
+
+        out.write_all(&[CELL_FIELDS.len() as u8])?;
+        for (name, size) in CELL_FIELDS {
+            write_string(&mut out, name)?;
+            out.write_all(&[size])?;
+        }
+
+        for (name, size) in CELL_FIELDS {
+            let mut bytes = Vec::with_capacity(self.cells.len() * size as usize);
+            for cell in &self.cells {
+                match name {
+                    "type" => bytes.extend(cell.get_type().0.to_le_bytes()),
+                    "vx" => bytes.extend(cell.vx.to_le_bytes()),
+                    "vy" => bytes.extend(cell.vy.to_le_bytes()),
+                    "moisture" => bytes.extend(cell.moisture.to_le_bytes()),
+                    "temperature" => bytes.extend(cell.temperature.to_le_bytes()),
+                    "latent_heat" => bytes.extend(cell.latent_heat.to_le_bytes()),
+                    "fuel" => bytes.extend(cell.fuel.to_le_bytes()),
+                    "last_updated" => bytes.push(cell.last_updated),
+                    "simulated_at" => bytes.extend(cell.simulated_at.to_le_bytes()),
+                    _ => unreachable!("unhandled cell field '{name}'"),
+                }
+            }
+            out.write_all(&bytes)?;
+        }
+
+        // Sleeping chunks are stored as well, so loaded saves continue exactly like the original.
+        let pending: Vec<_> = self.chunks.pending().collect();
+        out.write_all(&(pending.len() as u32).to_le_bytes())?;
+        for rect in pending {
+            match rect {
+                Some((min, max)) => {
+                    out.write_all(&[1])?;
+                    for value in [min.0, min.1, max.0, max.1] {
+                        out.write_all(&(value as u32).to_le_bytes())?;
+                    }
+                }
+                None => out.write_all(&[0])?,
+            }
+        }
+
+        out.finish()?;
+        Ok(())
+    }
+
+    pub fn load_from(mut reader: impl Read) -> Result<Self, SaveError> {
+        let mut magic = [0; 4];
+        read_exact(&mut reader, &mut magic)?;
+        if magic != MAGIC {
+            return Err(SaveError::InvalidMagic);
+        }
+
+        let version = u16::from_le_bytes(read_array(&mut reader)?);
+        if version > FORMAT_VERSION {
+            return Err(SaveError::UnsupportedVersion(version));
+        }
+
+        let mut input = BufReader::new(ZlibDecoder::new(reader));
+        let width = u32::from_le_bytes(read_array(&mut input)?) as usize;
+        let height = u32::from_le_bytes(read_array(&mut input)?) as usize;
+        let gravity = f32::from_le_bytes(read_array(&mut input)?);
+        let max_velocity = f32::from_le_bytes(read_array(&mut input)?);
+        let ambient_temperature = f32::from_le_bytes(read_array(&mut input)?);
+        let [update_counter] = read_array(&mut input)?;
+        // Older versions didn't count ticks, their cells start out with nothing to catch up on.
+        let ticks = if version >= 2 {
+            u32::from_le_bytes(read_array(&mut input)?)
+        } else {
+            0
+        };
+        let seed = u64::from_le_bytes(read_array(&mut input)?);
+
+        if !(gravity.is_finite() && ambient_temperature.is_finite()) {
+            return Err(SaveError::Corrupt("simulation parameters are invalid"));
+        }
+        // Cells can't move further than the halo of the parallel update in a single tick.
+        if !(0.0..=(HALO - 2) as f32).contains(&max_velocity) {
+            return Err(SaveError::Corrupt("maximum velocity is out of range"));
+        }
+
+        // Only a sandbox without any cells at all may have no columns or no rows.
+        if (width == 0) != (height == 0) || width > MAX_DIMENSION || height > MAX_DIMENSION {
+            return Err(SaveError::Corrupt("dimensions are out of range"));
+        }
+        let len = width
+            .checked_mul(height)
+            .ok_or(SaveError::Corrupt("dimensions are too large"))?;
+
+        let registry = MaterialRegistry::global();
+        let material_count = u16::from_le_bytes(read_array(&mut input)?);
+        let mut materials = Vec::with_capacity(material_count as usize);
+        for _ in 0..material_count {
+            let name = read_string(&mut input)?;
+            match registry.find(&name) {
+                Some(type_) => materials.push(type_),
+                None => return Err(SaveError::UnknownMaterial(name)),
+            }
+        }
+
+        let [field_count] = read_array(&mut input)?;
+        let mut fields = Vec::with_capacity(field_count as usize);
+        for _ in 0..field_count {
+            let name = read_string(&mut input)?;
+            let [size] = read_array(&mut input)?;
+            fields.push((name, size));
+        }
+
+        if fields.first().is_none_or(|(name, _)| name != "type") {
+            return Err(SaveError::Corrupt("cell types have to come first"));
+        }
+
+        // Not reserved up front, the dimensions of a corrupt file could be anything.
+        let mut cells = Vec::new();
+        for (name, size) in fields {
+            let expected = CELL_FIELDS
+                .iter()
+                .find(|(known, _)| *known == name)
+                .map(|(_, size)| *size);
+
+            let Some(expected) = expected else {
+                skip(&mut input, len as u64 * size as u64)?;
+                continue;
+            };
+
+            if size != expected {
+                return Err(SaveError::InvalidFieldSize {
+                    name,
+                    size,
+                    expected,
+                });
+            }
+
+            if name == "type" {
+                for _ in 0..len {
+                    let id = u16::from_le_bytes(read_array(&mut input)?);
+                    let type_ = materials
+                        .get(id as usize)
+                        .copied()
+                        .ok_or(SaveError::Corrupt("cell has an unknown material id"))?;
+                    cells.push(Cell::new(type_));
+                }
+                continue;
+            }
+
+            for cell in &mut cells {
+                match name.as_str() {
+                    "vx" => cell.vx = f32::from_le_bytes(read_array(&mut input)?),
+                    "vy" => cell.vy = f32::from_le_bytes(read_array(&mut input)?),
+                    "moisture" => cell.moisture = f32::from_le_bytes(read_array(&mut input)?),
+                    "temperature" => cell.temperature = f32::from_le_bytes(read_array(&mut input)?),
+                    "latent_heat" => cell.latent_heat = f32::from_le_bytes(read_array(&mut input)?),
+                    "fuel" => cell.fuel = f32::from_le_bytes(read_array(&mut input)?),
+                    "last_updated" => [cell.last_updated] = read_array(&mut input)?,
+                    "simulated_at" => {
+                        cell.simulated_at = u32::from_le_bytes(read_array(&mut input)?)
+                    }
+                    _ => unreachable!("unhandled cell field '{name}'"),
+                }
+            }
+        }
+
+        let chunk_count = u32::from_le_bytes(read_array(&mut input)?);
+        let mut pending = Vec::new();
+        for _ in 0..chunk_count {
+            let [awake] = read_array(&mut input)?;
+            if awake == 0 {
+                pending.push(None);
+                continue;
+            }
+
+            let mut read =
+                || read_array(&mut input).map(|bytes| u32::from_le_bytes(bytes) as usize);
+            pending.push(Some(((read()?, read()?), (read()?, read()?))));
+        }
+
+        let mut chunks = Chunks::new(width, height);
+        chunks.restore_pending(&pending);
+
+        Ok(Self {
+            cells,
+            width,
+            height,
+            gravity,
+            max_velocity,
+            ambient_temperature,
+            update_counter,
+            ticks,
+            chunks,
+            rng: Rng::with_seed(seed),
+        })
+    }
+}
+
+fn write_string(writer: &mut impl Write, value: &str) -> Result<(), SaveError> {
+    writer.write_all(&(value.len() as u16).to_le_bytes())?;
+    writer.write_all(value.as_bytes())?;
+    Ok(())
+}
+
+fn read_string(reader: &mut impl Read) -> Result<String, SaveError> {
+    let len = u16::from_le_bytes(read_array(reader)?);
+    let mut bytes = vec![0; len as usize];
+    read_exact(reader, &mut bytes)?;
+    String::from_utf8(bytes).map_err(|_| SaveError::Corrupt("name is not valid UTF-8"))
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> Result<[u8; N], SaveError> {
+    let mut bytes = [0; N];
+    read_exact(reader, &mut bytes)?;
+    Ok(bytes)
+}
+
+/// Reads the exact amount of bytes, treating truncated or garbled data as corruption.
+fn read_exact(reader: &mut impl Read, buf: &mut [u8]) -> Result<(), SaveError> {
+    reader.read_exact(buf).map_err(|err| match err.kind() {
+        ErrorKind::UnexpectedEof => SaveError::Corrupt("unexpected end of file"),
+        ErrorKind::InvalidInput | ErrorKind::InvalidData => {
+            SaveError::Corrupt("compressed data is invalid")
+        }
+        _ => SaveError::Io(err),
+    })
+}
+
+fn skip(reader: &mut impl Read, len: u64) -> Result<(), SaveError> {
+    let skipped = std::io::copy(&mut reader.take(len), &mut std::io::sink())?;
+    if skipped < len {
+        return Err(SaveError::Corrupt("unexpected end of file"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell::CellType;
+
+    fn saved(sandbox: &Sandbox) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        sandbox.save_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn sample() -> Sandbox {
+        let mut sandbox = Sandbox::with_seed(40, 30, 7);
+        for x in 5..35 {
+            sandbox.place(x, 29, Cell::new(CellType::WALL));
+            sandbox.place(x, 10, Cell::new(CellType::SAND));
+            sandbox.place(x, 12, Cell::new(CellType::WATER));
+        }
+        for _ in 0..20 {
+            sandbox.update();
+        }
+        sandbox
+    }
+
+    #[test]
+    fn round_trip_continues_identically() {
+        let mut original = sample();
+        let mut loaded = Sandbox::load_from(saved(&original).as_slice()).unwrap();
+        assert_eq!(
+            (loaded.width, loaded.height),
+            (original.width, original.height)
+        );
+
+        for _ in 0..50 {
+            original.update();
+            loaded.update();
+        }
+        assert_eq!(saved(&loaded), saved(&original));
+    }
+
+    #[test]
+    fn corrupt_files_are_rejected() {
+        let bytes = saved(&sample());
+
+        let mut garbage = bytes.clone();
+        garbage[0] = b'X';
+        assert!(matches!(
+            Sandbox::load_from(garbage.as_slice()),
+            Err(SaveError::InvalidMagic)
+        ));
+
+        let truncated = &bytes[..bytes.len() / 2];
+        assert!(matches!(
+            Sandbox::load_from(truncated),
+            Err(SaveError::Corrupt(_))
+        ));
+
+        // Huge dimensions in the header mustn't be trusted before the cells are actually there.
+        let huge = patched(&bytes, |body| body[..8].fill(0xFF));
+        assert!(matches!(
+            Sandbox::load_from(huge.as_slice()),
+            Err(SaveError::Corrupt(_))
+        ));
+        for (width, height) in [(0, u32::MAX), (7, 0), (MAX_DIMENSION as u32 + 1, 1)] {
+            let invalid = patched(&bytes, |body| {
+                body[..4].copy_from_slice(&width.to_le_bytes());
+                body[4..8].copy_from_slice(&height.to_le_bytes());
+            });
+            assert!(matches!(
+                Sandbox::load_from(invalid.as_slice()),
+                Err(SaveError::Corrupt("dimensions are out of range"))
+            ));
+        }
+
+        // Gravity, maximum velocity and ambient temperature follow the dimensions.
+        for (offset, value) in [
+            (12, -1.0),
+            (12, HALO as f32),
+            (8, f32::NAN),
+            (16, f32::INFINITY),
+        ] {
+            let invalid = patched(&bytes, |body| {
+                body[offset..offset + 4].copy_from_slice(&f32::to_le_bytes(value))
+            });
+            assert!(matches!(
+                Sandbox::load_from(invalid.as_slice()),
+                Err(SaveError::Corrupt(_))
+            ));
+        }
+    }
+
+    /// Changes the decompressed part of a save.
+    fn patched(bytes: &[u8], patch: impl FnOnce(&mut Vec<u8>)) -> Vec<u8> {
+        let mut body = Vec::new();
+        ZlibDecoder::new(&bytes[6..])
+            .read_to_end(&mut body)
+            .unwrap();
+        patch(&mut body);
+
+        let mut patched = bytes[..6].to_vec();
+        let mut out = ZlibEncoder::new(&mut patched, Compression::default());
+        out.write_all(&body).unwrap();
+        out.finish().unwrap();
+        patched
+    }
+}